@@ -0,0 +1,27 @@
+//! Compare hashing the info dict in place with `info_hash_only` against
+//! parsing the whole torrent first, on the ubuntu sample.
+
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rustorrent::parser::meta_info::MetaInfo;
+
+fn bench_info_hash(c: &mut Criterion) {
+    let bytes = fs::read("tests/ubuntu_sample.torrent").unwrap();
+    assert_eq!(
+        MetaInfo::info_hash_only(&bytes).unwrap(),
+        MetaInfo::from_bytes(&bytes).unwrap().info.info_hash()
+    );
+
+    let mut group = c.benchmark_group("info_hash");
+    group.bench_function("info_hash_only", |b| {
+        b.iter(|| MetaInfo::info_hash_only(&bytes).unwrap())
+    });
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| MetaInfo::from_bytes(&bytes).unwrap().info.info_hash())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_info_hash);
+criterion_main!(benches);
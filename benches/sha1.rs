@@ -0,0 +1,46 @@
+//! Compare the SHA1 backends on the workloads that matter: whole pieces,
+//! hashed when verifying downloads, and info dicts, hashed for the info_hash.
+//! Run with `cargo bench --all-features` to include the OpenSSL backend.
+
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+#[cfg(feature = "openssl-sha1")]
+use rustorrent::hash::OpenSslSha1;
+use rustorrent::hash::{RustSha1, Sha1Backend};
+use rustorrent::parser::meta_info::MetaInfo;
+
+fn backends() -> Vec<(&'static str, Box<dyn Sha1Backend>)> {
+    #[allow(unused_mut)]
+    let mut backends: Vec<(&'static str, Box<dyn Sha1Backend>)> =
+        vec![("rust", Box::new(RustSha1))];
+    #[cfg(feature = "openssl-sha1")]
+    backends.push(("openssl", Box::new(OpenSslSha1)));
+    backends
+}
+
+fn bench_backends(c: &mut Criterion) {
+    let info = MetaInfo::from_file("tests/ubuntu_sample.torrent")
+        .unwrap()
+        .info;
+    let piece = fs::read("tests/ubuntu_sample.torrent")
+        .unwrap()
+        .into_iter()
+        .cycle()
+        .take(info.piece_length as usize)
+        .collect::<Vec<u8>>();
+
+    let mut group = c.benchmark_group("sha1");
+    for (name, data) in [("info dict", &info.bencode_value), ("piece", &piece)] {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        for (backend_name, backend) in backends() {
+            group.bench_with_input(BenchmarkId::new(backend_name, name), data, |b, data| {
+                b.iter(|| backend.digest(data))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_backends);
+criterion_main!(benches);
@@ -0,0 +1 @@
+pub mod routing_table;
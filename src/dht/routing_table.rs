@@ -0,0 +1,267 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// Number of nodes a bucket holds (the `k` of Kademlia)
+pub const K: usize = 8;
+
+/// 160-bit identifier of a DHT node, in the same space as info hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub [u8; 20]);
+
+impl NodeId {
+    /// XOR distance to another id. Distances compare as 160-bit big-endian
+    /// numbers, which is how the derived `Ord` compares the bytes.
+    pub fn distance(&self, other: &NodeId) -> NodeId {
+        let mut distance = [0; 20];
+        for (byte, (a, b)) in distance.iter_mut().zip(self.0.iter().zip(&other.0)) {
+            *byte = a ^ b;
+        }
+        NodeId(distance)
+    }
+
+    /// Number of leading bits both ids have in common
+    fn common_prefix_len(&self, other: &NodeId) -> usize {
+        let distance = self.distance(other);
+        match distance.0.iter().position(|&byte| byte != 0) {
+            Some(index) => index * 8 + distance.0[index].leading_zeros() as usize,
+            None => 160,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub id: NodeId,
+    pub addr: SocketAddr,
+    pub last_seen: Instant,
+}
+
+/// What [`RoutingTable::insert`] did with a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertOutcome {
+    Added,
+    /// the node was already known, it is now the most recently seen of its bucket
+    Updated,
+    /// The bucket is full and can't be split. The caller should ping
+    /// `least_recently_seen`: if it answers, [`RoutingTable::insert`] it
+    /// again to refresh it and drop the new node, otherwise
+    /// [`RoutingTable::evict`] it in favor of the new node.
+    BucketFull {
+        least_recently_seen: Node,
+    },
+    /// our own id, which never goes in the table
+    Ignored,
+}
+
+/// Kademlia routing table (BEP 5). Bucket `i` holds the nodes sharing
+/// exactly `i` leading bits with our id, except the last bucket which
+/// holds every node sharing at least as many. Only the last bucket is
+/// split when full, so the table knows more nodes close to us than far away.
+#[derive(Debug)]
+pub struct RoutingTable {
+    own_id: NodeId,
+    /// nodes of each bucket, least recently seen first
+    buckets: Vec<Vec<Node>>,
+}
+
+impl RoutingTable {
+    pub fn new(own_id: NodeId) -> Self {
+        Self {
+            own_id,
+            buckets: vec![Vec::new()],
+        }
+    }
+
+    pub fn own_id(&self) -> NodeId {
+        self.own_id
+    }
+
+    /// Add a node we heard from, or refresh it if it is already known
+    pub fn insert(&mut self, node: Node) -> InsertOutcome {
+        if node.id == self.own_id {
+            return InsertOutcome::Ignored;
+        }
+        loop {
+            let index = self.bucket_index(&node.id);
+            let num_buckets = self.buckets.len();
+            let bucket = &mut self.buckets[index];
+            if let Some(position) = bucket.iter().position(|known| known.id == node.id) {
+                bucket.remove(position);
+                bucket.push(node);
+                return InsertOutcome::Updated;
+            }
+            if bucket.len() < K {
+                bucket.push(node);
+                return InsertOutcome::Added;
+            }
+            if index != num_buckets - 1 || num_buckets == 160 {
+                return InsertOutcome::BucketFull {
+                    least_recently_seen: bucket[0].clone(),
+                };
+            }
+            self.split_last_bucket();
+        }
+    }
+
+    /// Replace a node that didn't answer our ping with `replacement`.
+    /// Returns `false` if `stale` isn't in the table.
+    pub fn evict(&mut self, stale: &NodeId, replacement: Node) -> bool {
+        let index = self.bucket_index(stale);
+        let bucket = &mut self.buckets[index];
+        let Some(position) = bucket.iter().position(|known| known.id == *stale) else {
+            return false;
+        };
+        bucket.remove(position);
+        self.insert(replacement);
+        true
+    }
+
+    /// Up to `k` known nodes, the closest to `target` first
+    pub fn closest(&self, target: &NodeId, k: usize) -> Vec<&Node> {
+        let mut nodes: Vec<&Node> = self.buckets.iter().flatten().collect();
+        nodes.sort_by_key(|node| node.id.distance(target));
+        nodes.truncate(k);
+        nodes
+    }
+
+    pub fn num_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(Vec::is_empty)
+    }
+
+    fn bucket_index(&self, id: &NodeId) -> usize {
+        self.own_id
+            .common_prefix_len(id)
+            .min(self.buckets.len() - 1)
+    }
+
+    /// Move the nodes of the last bucket sharing one more bit with our id
+    /// into a new bucket
+    fn split_last_bucket(&mut self) {
+        let own_id = self.own_id;
+        let split_at = self.buckets.len();
+        let last = self.buckets.last_mut().expect("there is always a bucket");
+        let (closer, farther) = last
+            .drain(..)
+            .partition(|node| own_id.common_prefix_len(&node.id) >= split_at);
+        *last = farther;
+        self.buckets.push(closer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(first_byte: u8, last_byte: u8) -> NodeId {
+        let mut id = [0; 20];
+        id[0] = first_byte;
+        id[19] = last_byte;
+        NodeId(id)
+    }
+
+    fn node(id: NodeId, now: Instant) -> Node {
+        Node {
+            id,
+            addr: SocketAddr::from(([10, 0, 0, id.0[19]], 6881)),
+            last_seen: now,
+        }
+    }
+
+    #[test]
+    fn should_compute_xor_distances() {
+        assert_eq!(id(0b1010, 1).distance(&id(0b0110, 3)), id(0b1100, 2));
+        assert_eq!(id(0x80, 0).common_prefix_len(&id(0, 0)), 0);
+        assert_eq!(id(0x01, 0).common_prefix_len(&id(0, 0)), 7);
+        assert_eq!(id(0, 1).common_prefix_len(&id(0, 0)), 159);
+        assert_eq!(id(0, 0).common_prefix_len(&id(0, 0)), 160);
+    }
+
+    #[test]
+    fn should_return_the_closest_nodes_by_xor_distance() {
+        let now = Instant::now();
+        let mut table = RoutingTable::new(id(0, 0));
+        for first_byte in [0x80, 0x40, 0x20, 0x10, 0x08, 0xc0] {
+            table.insert(node(id(first_byte, first_byte), now));
+        }
+
+        let target = id(0x50, 0);
+        let closest: Vec<NodeId> = table
+            .closest(&target, 4)
+            .into_iter()
+            .map(|node| node.id)
+            .collect();
+        // distances: 0x40 -> 0x10, 0x10 -> 0x40, 0x20 -> 0x70, 0x08 -> 0x58
+        assert_eq!(
+            closest,
+            vec![
+                id(0x40, 0x40),
+                id(0x10, 0x10),
+                id(0x08, 0x08),
+                id(0x20, 0x20)
+            ]
+        );
+        assert_eq!(table.closest(&target, 10).len(), 6);
+    }
+
+    #[test]
+    fn should_split_the_bucket_covering_our_id() {
+        let now = Instant::now();
+        let mut table = RoutingTable::new(id(0, 0));
+        // far from us: none of these share the first bit with our id
+        for last_byte in 1..=K as u8 {
+            assert_eq!(
+                table.insert(node(id(0x80, last_byte), now)),
+                InsertOutcome::Added
+            );
+        }
+        assert_eq!(table.num_buckets(), 1);
+
+        // the only bucket covers our id, so it is split to make room
+        assert_eq!(table.insert(node(id(0x01, 100), now)), InsertOutcome::Added);
+        assert!(table.num_buckets() > 1);
+        assert_eq!(table.len(), K + 1);
+        assert_eq!(table.insert(node(id(0, 0), now)), InsertOutcome::Ignored);
+    }
+
+    #[test]
+    fn should_ask_to_ping_the_least_recently_seen_node_of_a_full_bucket() {
+        let now = Instant::now();
+        let mut table = RoutingTable::new(id(0, 0));
+        for last_byte in 1..=K as u8 {
+            table.insert(node(id(0x80, last_byte), now));
+        }
+        // refreshing the first node makes the second the least recently seen
+        assert_eq!(table.insert(node(id(0x80, 1), now)), InsertOutcome::Updated);
+        // split once for our side, the far bucket can't be split anymore
+        table.insert(node(id(0x01, 100), now));
+
+        let newcomer = node(id(0x80, 50), now);
+        let InsertOutcome::BucketFull {
+            least_recently_seen,
+        } = table.insert(newcomer.clone())
+        else {
+            panic!("expected the far bucket to be full");
+        };
+        assert_eq!(least_recently_seen.id, id(0x80, 2));
+
+        // it didn't answer the ping
+        assert!(table.evict(&least_recently_seen.id, newcomer));
+        assert!(!table.evict(&least_recently_seen.id, node(id(0x80, 51), now)));
+        let far: Vec<NodeId> = table
+            .closest(&id(0x80, 0), K)
+            .into_iter()
+            .map(|node| node.id)
+            .collect();
+        assert!(far.contains(&id(0x80, 50)));
+        assert!(!far.contains(&id(0x80, 2)));
+        assert_eq!(table.len(), K + 1);
+    }
+}
@@ -0,0 +1,83 @@
+//! SHA1 hashing, used for info hashes and piece verification.
+//!
+//! Hashing dominates the CPU time spent checking large torrents, so the
+//! implementation is pluggable: the `openssl-sha1` feature switches
+//! [`sha1`] to OpenSSL's hand-tuned assembly instead of the `sha1` crate.
+
+use sha1::{Digest, Sha1};
+
+/// A SHA1 implementation
+pub trait Sha1Backend {
+    fn digest(&self, data: &[u8]) -> [u8; 20];
+}
+
+/// Pure Rust implementation from the `sha1` crate,
+/// which already uses the SHA CPU extensions when available at runtime.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RustSha1;
+
+impl Sha1Backend for RustSha1 {
+    fn digest(&self, data: &[u8]) -> [u8; 20] {
+        Sha1::digest(data).into()
+    }
+}
+
+/// OpenSSL's implementation, with assembly tuned for most CPUs
+#[cfg(feature = "openssl-sha1")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OpenSslSha1;
+
+#[cfg(feature = "openssl-sha1")]
+impl Sha1Backend for OpenSslSha1 {
+    fn digest(&self, data: &[u8]) -> [u8; 20] {
+        openssl::sha::sha1(data)
+    }
+}
+
+/// Backend selected by the enabled features
+#[cfg(feature = "openssl-sha1")]
+pub type DefaultSha1 = OpenSslSha1;
+/// Backend selected by the enabled features
+#[cfg(not(feature = "openssl-sha1"))]
+pub type DefaultSha1 = RustSha1;
+
+/// Hash the data with the [`DefaultSha1`] backend
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    DefaultSha1::default().digest(data)
+}
+
+/// Lowercase hex of a digest, the way info hashes and md5sums are written
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::parser::meta_info::MetaInfo;
+
+    use super::*;
+
+    #[test]
+    fn should_hash_the_info_dict_into_the_info_hash() {
+        let bytes = fs::read("tests/ubuntu_sample.torrent").unwrap();
+        let info = MetaInfo::from_bytes(&bytes).unwrap().info;
+
+        assert_eq!(
+            hex(&sha1(&info.bencode_value)),
+            "99c82bb73505a3c0b453f9fa0e881d6e5a32a0c1"
+        );
+    }
+
+    #[cfg(feature = "openssl-sha1")]
+    #[test]
+    fn should_produce_the_same_digests_with_every_backend() {
+        let bytes = fs::read("tests/ubuntu_sample.torrent").unwrap();
+        let info = MetaInfo::from_bytes(&bytes).unwrap().info;
+
+        for data in [&info.bencode_value[..], b"", &bytes] {
+            assert_eq!(RustSha1.digest(data), OpenSslSha1.digest(data));
+        }
+    }
+}
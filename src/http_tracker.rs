@@ -1,35 +1,319 @@
-use crate::parser::announce_info::AnnounceInfo;
-use crate::parser::{bencode::BencodeParser, meta_info::Info};
-use reqwest::Client;
-use sha1::{Digest, Sha1};
+use crate::parser::announce_info::{AnnounceError, AnnounceInfo};
+use crate::parser::bencode::{BencodeErrorKind, BencodeParser};
+use crate::parser::meta_info::Info;
+use crate::parser::metrics::ParseMetrics;
+use crate::parser::scrape_info::ScrapeInfo;
+use indexmap::IndexMap;
+use reqwest::{Client, Proxy, Response};
+use std::error::Error;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Maximum size of a tracker response, once decompressed.
+/// Even with hundreds of peers, announce responses only take a few KiB.
+pub const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// The tracker response is bigger than [`MAX_RESPONSE_SIZE`],
+/// e.g. a small gzip stream that decompresses to gigabytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseTooLarge {
+    pub limit: usize,
+}
+
+impl Error for ResponseTooLarge {}
+
+impl Display for ResponseTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tracker response exceeds the maximum of {} bytes",
+            self.limit
+        )
+    }
+}
+
+/// Event reported to the tracker along with an announce.
+/// Regular periodic announces don't carry any event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnounceEvent {
+    Started,
+    Completed,
+    Stopped,
+}
+
+impl AnnounceEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnnounceEvent::Started => "started",
+            AnnounceEvent::Completed => "completed",
+            AnnounceEvent::Stopped => "stopped",
+        }
+    }
+}
+
+/// Parameters sent to the tracker on every announce.
+/// Built with fluent setters on top of the defaults, e.g.
+/// `AnnounceRequest::default().left(1024).event(AnnounceEvent::Started)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnounceRequest {
+    /// port number our client is listening on
+    pub port: u16,
+    /// total amount of bytes uploaded since the `started` event
+    pub uploaded: u64,
+    /// total amount of bytes downloaded since the `started` event
+    pub downloaded: u64,
+    /// number of bytes this client still has to download
+    pub left: u64,
+    /// whether we accept the compact (binary) peer list format
+    pub compact: bool,
+    pub event: Option<AnnounceEvent>,
+    /// number of peers we would like to receive from the tracker
+    pub numwant: Option<u32>,
+    /// random value used by the tracker to identify us if our IP changes
+    pub key: Option<String>,
+    /// `tracker id` received in a previous announce response
+    pub tracker_id: Option<String>,
+    /// IP address to advertise instead of the one the request comes from
+    pub ip: Option<String>,
+    /// BEP 7: our IPv4 address, so dual-stack trackers can return peers of both families
+    pub ipv4: Option<String>,
+    /// BEP 7: our IPv6 address, so dual-stack trackers can return peers of both families
+    pub ipv6: Option<String>,
+}
+
+impl Default for AnnounceRequest {
+    fn default() -> Self {
+        Self {
+            port: 6889,
+            uploaded: 0,
+            downloaded: 0,
+            left: 0,
+            compact: true,
+            event: None,
+            numwant: None,
+            key: None,
+            tracker_id: None,
+            ip: None,
+            ipv4: None,
+            ipv6: None,
+        }
+    }
+}
+
+impl AnnounceRequest {
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn uploaded(mut self, uploaded: u64) -> Self {
+        self.uploaded = uploaded;
+        self
+    }
+
+    pub fn downloaded(mut self, downloaded: u64) -> Self {
+        self.downloaded = downloaded;
+        self
+    }
+
+    pub fn left(mut self, left: u64) -> Self {
+        self.left = left;
+        self
+    }
+
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    pub fn event(mut self, event: AnnounceEvent) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    pub fn numwant(mut self, numwant: u32) -> Self {
+        self.numwant = Some(numwant);
+        self
+    }
+
+    pub fn key<S: Into<String>>(mut self, key: S) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    pub fn tracker_id<S: Into<String>>(mut self, tracker_id: S) -> Self {
+        self.tracker_id = Some(tracker_id.into());
+        self
+    }
+
+    pub fn ip<S: Into<String>>(mut self, ip: S) -> Self {
+        self.ip = Some(ip.into());
+        self
+    }
+
+    pub fn ipv4<S: Into<String>>(mut self, ipv4: S) -> Self {
+        self.ipv4 = Some(ipv4.into());
+        self
+    }
+
+    pub fn ipv6<S: Into<String>>(mut self, ipv6: S) -> Self {
+        self.ipv6 = Some(ipv6.into());
+        self
+    }
+
+    /// Query parameters for this request, excluding `info_hash` and `peer_id`
+    /// which must not be URL encoded a second time.
+    pub fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("port", self.port.to_string()),
+            ("uploaded", self.uploaded.to_string()),
+            ("downloaded", self.downloaded.to_string()),
+            ("left", self.left.to_string()),
+            (
+                "compact",
+                String::from(if self.compact { "1" } else { "0" }),
+            ),
+        ];
+        if let Some(event) = self.event {
+            pairs.push(("event", String::from(event.as_str())));
+        }
+        if let Some(numwant) = self.numwant {
+            pairs.push(("numwant", numwant.to_string()));
+        }
+        if let Some(key) = &self.key {
+            pairs.push(("key", key.clone()));
+        }
+        if let Some(tracker_id) = &self.tracker_id {
+            pairs.push(("trackerid", tracker_id.clone()));
+        }
+        if let Some(ip) = &self.ip {
+            pairs.push(("ip", ip.clone()));
+        }
+        if let Some(ipv4) = &self.ipv4 {
+            pairs.push(("ipv4", ipv4.clone()));
+        }
+        if let Some(ipv6) = &self.ipv6 {
+            pairs.push(("ipv6", ipv6.clone()));
+        }
+        pairs
+    }
+}
+
+/// The tracker doesn't support scraping: by convention, only trackers
+/// whose announce URL ends with an `announce` path segment do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrapeUnsupported {
+    pub announce_url: String,
+}
+
+impl Error for ScrapeUnsupported {}
+
+impl Display for ScrapeUnsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no scrape URL can be derived from the announce URL '{}'",
+            self.announce_url
+        )
+    }
+}
+
+/// Derive the scrape URL of a tracker by replacing `announce` with `scrape`
+/// at the start of the last path segment, e.g. `http://t/x/announce.php?k=1`
+/// becomes `http://t/x/scrape.php?k=1`.
+pub fn scrape_url(announce_url: &str) -> Result<String, ScrapeUnsupported> {
+    let unsupported = || ScrapeUnsupported {
+        announce_url: announce_url.to_string(),
+    };
+    let path_end = announce_url.find('?').unwrap_or(announce_url.len());
+    // the `//` of the scheme isn't part of the path
+    let authority_start = announce_url[..path_end]
+        .find("://")
+        .map_or(0, |scheme_end| scheme_end + 3);
+    let path_start = authority_start
+        + announce_url[authority_start..path_end]
+            .find('/')
+            .ok_or_else(unsupported)?;
+    let segment_start = announce_url[path_start..path_end]
+        .rfind('/')
+        .map_or(0, |slash| path_start + slash + 1);
+    match announce_url[segment_start..].strip_prefix("announce") {
+        Some(rest) => Ok(format!("{}scrape{}", &announce_url[..segment_start], rest)),
+        None => Err(unsupported()),
+    }
+}
 
 /// Handle HTTP trackers providing torrent information.
 /// Mostly following the (unofficial) spec from [wiki.theory.org](https://wiki.theory.org/BitTorrentSpecification#Tracker_Request_Parameters)
-pub struct HTTPTracker<'a> {
-    peer_id: &'a str,
+pub struct HTTPTracker {
+    peer_id: [u8; 20],
     http_client: Client,
+    metrics: Option<Arc<dyn ParseMetrics + Send + Sync>>,
 }
 
-impl<'a> HTTPTracker<'a> {
-    pub fn new(peer_id: &'a str, http_client: Client) -> Self {
+impl HTTPTracker {
+    /// Create a tracker announcing ourselves with the given peer id
+    /// (see [`generate_peer_id`](crate::peer::peer_id::generate_peer_id)), using the given HTTP client.
+    ///
+    /// Clients announce to their trackers periodically for as long as they run,
+    /// so the same client (see [`HTTPTracker::default_client`]) should be shared
+    /// by all trackers: its pooled keep-alive connections are then reused across
+    /// announces instead of opening a new connection every time.
+    /// Cloning a [`Client`] is cheap and shares the pool.
+    pub fn new(peer_id: [u8; 20], http_client: Client) -> Self {
         Self {
             peer_id,
             http_client,
+            metrics: None,
         }
     }
 
+    /// Report why tracker responses fail to parse to the given metrics,
+    /// e.g. to chart how many responses are truncated.
+    /// Failures reported by the tracker itself aren't parse errors.
+    pub fn metrics(mut self, metrics: Arc<dyn ParseMetrics + Send + Sync>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// HTTP client tuned for periodic announces: idle connections are kept
+    /// in the pool for longer than common announce intervals, and TCP
+    /// keep-alive stops middleboxes from silently dropping them.
+    pub fn default_client() -> Client {
+        Client::builder()
+            .pool_idle_timeout(Duration::from_secs(30 * 60))
+            .tcp_keepalive(Duration::from_secs(60))
+            .build()
+            .expect("the default HTTP client configuration is valid")
+    }
+
+    /// Create a tracker whose requests are all routed through the given proxy.
+    /// Both HTTP(S) (`http://host:port`) and SOCKS5 (`socks5://host:port`)
+    /// proxy URLs are supported, credentials can be passed in the URL.
+    pub fn with_proxy(peer_id: [u8; 20], proxy_url: &str) -> Result<Self, reqwest::Error> {
+        let http_client = Client::builder().proxy(Proxy::all(proxy_url)?).build()?;
+        Ok(Self::new(peer_id, http_client))
+    }
+
     pub async fn get_announce_info(
         &self,
         url: &str,
         info: Info,
-    ) -> Result<AnnounceInfo, Box<dyn std::error::Error>> {
-        let info_hash = Self::generate_hash(&info.bencode_value);
-        // TODO: generate a peer ID during client boot?
-        // Probably read something from the build config and
-        // use some sort of string generator as a suffix for each user.
-        // We should persist this so we can reuse the same client ID
-        // between app boots.
-        let peer_id = Self::generate_hash(&self.peer_id.as_bytes().to_vec());
+        request: &AnnounceRequest,
+    ) -> Result<AnnounceInfo, Box<dyn Error>> {
+        self.announce(url, &info.info_hash(), request).await
+    }
+
+    /// Announce ourselves for the torrent identified by the given info_hash
+    pub async fn announce(
+        &self,
+        url: &str,
+        info_hash: &[u8; 20],
+        request: &AnnounceRequest,
+    ) -> Result<AnnounceInfo, Box<dyn Error>> {
+        let info_hash = urlencoding::encode_binary(info_hash);
+        let peer_id = urlencoding::encode_binary(&self.peer_id);
 
         // when using reqwest query methods, the info_hash and peer_id
         // will be URL encoded again, which modifies the binary string.
@@ -41,30 +325,69 @@ impl<'a> HTTPTracker<'a> {
         let response = self
             .http_client
             .get(url_with_hash)
-            .query(&[
-                ("port", String::from("6889")),
-                ("uploaded", String::from("0")),
-                ("downloaded", String::from("0")),
-                ("left", info.piece_length.to_string()),
-                ("compact", String::from("1")),
-                ("event", String::from("started")),
-            ])
+            .query(&request.query_pairs())
             .send()
-            .await?
-            .bytes()
             .await?;
+        let response = Self::read_body(response, MAX_RESPONSE_SIZE).await?;
 
-        let bencode_resp = BencodeParser::decode(&response)?;
-        let announce_info = AnnounceInfo::parse(&bencode_resp)?;
+        let bencode_resp = BencodeParser::decode(&response)
+            .inspect_err(|err| self.record_parse_error(err.kind()))?;
+        let announce_info = AnnounceInfo::parse(&bencode_resp)
+            .inspect_err(|err| self.record_malformed_response(err))?;
 
         Ok(announce_info)
     }
 
-    fn generate_hash(value: &Vec<u8>) -> String {
-        let mut hasher = Sha1::new();
-        hasher.update(value);
-        let bytes = hasher.finalize();
-        urlencoding::encode_binary(&bytes).into_owned()
+    /// Get the stats (seeders, leechers...) of the given torrents without
+    /// joining their swarms. Torrents the tracker doesn't know about are
+    /// left out of the returned map.
+    pub async fn scrape(
+        &self,
+        announce_url: &str,
+        info_hashes: &[[u8; 20]],
+    ) -> Result<IndexMap<[u8; 20], ScrapeInfo>, Box<dyn Error>> {
+        // info hashes are appended as is, see `announce`
+        let mut url = scrape_url(announce_url)?;
+        for info_hash in info_hashes {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            url.push(separator);
+            url.push_str("info_hash=");
+            url.push_str(&urlencoding::encode_binary(info_hash));
+        }
+
+        let response = self.http_client.get(url).send().await?;
+        let response = Self::read_body(response, MAX_RESPONSE_SIZE).await?;
+        let bencode_resp = BencodeParser::decode(&response)
+            .inspect_err(|err| self.record_parse_error(err.kind()))?;
+        Ok(ScrapeInfo::parse_files(&bencode_resp)
+            .inspect_err(|err| self.record_malformed_response(err))?)
+    }
+
+    fn record_parse_error(&self, kind: BencodeErrorKind) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_error(kind);
+        }
+    }
+
+    /// Responses that decode but aren't valid announce or scrape responses
+    /// are invalid ones, unlike failures reported by the tracker
+    fn record_malformed_response(&self, err: &AnnounceError) {
+        if let AnnounceError::MalformedResponse(_) = err {
+            self.record_parse_error(BencodeErrorKind::Invalid);
+        }
+    }
+
+    /// Read the (decompressed) body chunk by chunk, giving up as soon as it
+    /// grows past `limit` rather than buffering whatever the tracker sends.
+    async fn read_body(mut response: Response, limit: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                return Err(Box::new(ResponseTooLarge { limit }));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
     }
 }
 
@@ -72,10 +395,21 @@ impl<'a> HTTPTracker<'a> {
 mod tests {
 
     use std::fs;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
 
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
     use wiremock::ResponseTemplate;
 
+    use crate::parser::bencode::BencodeError;
     use crate::parser::meta_info::MetaInfo;
+    use crate::parser::metrics::ErrorCounters;
+    use crate::peer::peer_id::generate_peer_id;
 
     use super::*;
 
@@ -96,11 +430,415 @@ mod tests {
 
         // example of a valid announce URL:
         // https://torrent.ubuntu.com/announce?info_hash=%99%C8%2B%B75%05%A3%C0%B4S%F9%FA%0E%88%1DnZ2%A0%C1&peer_id=%B7%C0%9B%A8%FC%DC%FB%91%C1N%AE%8D%DBZ%E2b%F2%84%B6%E5&port=8888&uploaded=0&downloaded=0&left=555555&compact=1&event=started
-        let http_tracker = HTTPTracker::new("rustorrent-client-dev", Client::new());
+        let http_tracker = HTTPTracker::new(generate_peer_id("-RT0001-"), Client::new());
         let resp = http_tracker
-            .get_announce_info(&mock_server.uri(), meta_info.info)
+            .get_announce_info(
+                &mock_server.uri(),
+                meta_info.info,
+                &AnnounceRequest::default().event(AnnounceEvent::Started),
+            )
             .await;
 
         assert!(resp.is_ok());
     }
+
+    #[tokio::test]
+    async fn should_send_the_raw_peer_id() {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::query_param(
+            "peer_id",
+            "-RT0001-abcdefghijkl",
+        ))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_bytes(fs::read("tests/announce_response").unwrap()),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        let http_tracker = HTTPTracker::new(*b"-RT0001-abcdefghijkl", Client::new());
+        let announce_info = http_tracker
+            .announce(&mock_server.uri(), &[1; 20], &AnnounceRequest::default())
+            .await;
+        assert!(announce_info.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_send_the_announce_request_parameters() {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::query_param("port", "6881"))
+            .and(wiremock::matchers::query_param("uploaded", "1024"))
+            .and(wiremock::matchers::query_param("downloaded", "2048"))
+            .and(wiremock::matchers::query_param("left", "4096"))
+            .and(wiremock::matchers::query_param("event", "stopped"))
+            .and(wiremock::matchers::query_param("numwant", "25"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(fs::read("tests/announce_response").unwrap()),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let request = AnnounceRequest::default()
+            .port(6881)
+            .uploaded(1024)
+            .downloaded(2048)
+            .left(4096)
+            .event(AnnounceEvent::Stopped)
+            .numwant(25);
+        let http_tracker = HTTPTracker::new(generate_peer_id("-RT0001-"), Client::new());
+        let announce_info = http_tracker
+            .announce(&mock_server.uri(), &[1; 20], &request)
+            .await;
+        assert!(announce_info.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_announce_for_both_address_families() {
+        let meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        let mut body =
+            b"d8:completei1e10:incompletei0e8:intervali1800e5:peersld2:ip8:10.0.0.57:peer id3:abc4:porti6881eee6:peers618:"
+                .to_vec();
+        body.extend(std::net::Ipv6Addr::LOCALHOST.octets());
+        body.extend(6882u16.to_be_bytes());
+        body.push(b'e');
+
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::query_param("ipv4", "10.0.0.1"))
+            .and(wiremock::matchers::query_param("ipv6", "::2"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let http_tracker = HTTPTracker::new(generate_peer_id("-RT0001-"), Client::new());
+        let announce_info = http_tracker
+            .get_announce_info(
+                &mock_server.uri(),
+                meta_info.info,
+                &AnnounceRequest::default().ipv4("10.0.0.1").ipv6("::2"),
+            )
+            .await
+            .unwrap();
+
+        let ips: Vec<&str> = announce_info
+            .peers
+            .iter()
+            .map(|peer| peer.ip.as_str())
+            .collect();
+        assert_eq!(ips, vec!["10.0.0.5", "::1"]);
+    }
+
+    #[tokio::test]
+    async fn should_route_announces_through_the_proxy() {
+        let meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        let decoded_announce_response = fs::read("tests/announce_response").unwrap();
+
+        let proxy = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/announce"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(decoded_announce_response))
+            .expect(1)
+            .mount(&proxy)
+            .await;
+
+        // The tracker host doesn't exist, so the announce only
+        // succeeds if the request is sent to the proxy instead.
+        let http_tracker =
+            HTTPTracker::with_proxy(generate_peer_id("-RT0001-"), &proxy.uri()).unwrap();
+        let resp = http_tracker
+            .get_announce_info(
+                "http://tracker.invalid/announce",
+                meta_info.info,
+                &AnnounceRequest::default(),
+            )
+            .await;
+
+        assert!(resp.is_ok());
+    }
+
+    #[test]
+    fn should_derive_the_scrape_url_from_the_announce_url() {
+        assert_eq!(
+            scrape_url("http://example.com/announce").unwrap(),
+            "http://example.com/scrape"
+        );
+        assert_eq!(
+            scrape_url("http://example.com/x/announce.php?passkey=abc").unwrap(),
+            "http://example.com/x/scrape.php?passkey=abc"
+        );
+        assert_eq!(
+            scrape_url("http://example.com/announce?path=/a").unwrap(),
+            "http://example.com/scrape?path=/a"
+        );
+        for announce_url in [
+            "http://example.com/a",
+            "http://example.com/x/announce/foo",
+            "http://announce.example.com",
+            "http://announce.example.com?k=/announce",
+        ] {
+            assert_eq!(
+                scrape_url(announce_url).unwrap_err().to_string(),
+                format!(
+                    "no scrape URL can be derived from the announce URL '{}'",
+                    announce_url
+                )
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_scrape_the_stats_of_every_torrent() {
+        let mut body = b"d5:filesd20:".to_vec();
+        body.extend([b'a'; 20]);
+        body.extend(b"d8:completei3e10:downloadedi40e10:incompletei2eeee");
+
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/scrape"))
+            .and(wiremock::matchers::query_param("info_hash", "a".repeat(20)))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let http_tracker = HTTPTracker::new(generate_peer_id("-RT0001-"), Client::new());
+        let stats = http_tracker
+            .scrape(
+                &format!("{}/announce", mock_server.uri()),
+                &[[b'a'; 20], [b'b'; 20]],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(
+            stats.get(&[b'a'; 20]),
+            Some(&ScrapeInfo {
+                complete: 3,
+                downloaded: 40,
+                incomplete: 2
+            })
+        );
+        let requests = mock_server.received_requests().await.unwrap();
+        assert_eq!(requests[0].url.query_pairs().count(), 2);
+    }
+
+    #[test]
+    fn should_accept_socks5_proxies() {
+        assert!(
+            HTTPTracker::with_proxy(generate_peer_id("-RT0001-"), "socks5://127.0.0.1:1080")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn should_build_query_from_announce_request() {
+        let request = AnnounceRequest::default()
+            .port(6881)
+            .uploaded(10)
+            .downloaded(20)
+            .left(30)
+            .compact(false)
+            .event(AnnounceEvent::Completed)
+            .numwant(50)
+            .key("abc")
+            .tracker_id("tracker-1")
+            .ip("10.0.0.1")
+            .ipv4("10.0.0.2")
+            .ipv6("::1");
+
+        assert_eq!(
+            request.query_pairs(),
+            vec![
+                ("port", String::from("6881")),
+                ("uploaded", String::from("10")),
+                ("downloaded", String::from("20")),
+                ("left", String::from("30")),
+                ("compact", String::from("0")),
+                ("event", String::from("completed")),
+                ("numwant", String::from("50")),
+                ("key", String::from("abc")),
+                ("trackerid", String::from("tracker-1")),
+                ("ip", String::from("10.0.0.1")),
+                ("ipv4", String::from("10.0.0.2")),
+                ("ipv6", String::from("::1")),
+            ]
+        );
+    }
+
+    /// Serve the announce response over plain HTTP/1.1 keep-alive connections,
+    /// counting how many connections were opened.
+    async fn counting_tracker() -> (String, Arc<AtomicUsize>) {
+        let body = fs::read("tests/announce_response").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/announce", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let mut received: Vec<u8> = Vec::new();
+                    let mut buf = [0; 1024];
+                    loop {
+                        let Ok(read @ 1..) = socket.read(&mut buf).await else {
+                            return;
+                        };
+                        received.extend(&buf[..read]);
+                        // announces are GET requests without a body
+                        while let Some(end) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+                            received.drain(..end + 4);
+                            let head = format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                                body.len()
+                            );
+                            socket.write_all(head.as_bytes()).await.unwrap();
+                            socket.write_all(&body).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        (url, connections)
+    }
+
+    #[tokio::test]
+    async fn should_reuse_the_connection_across_announces() {
+        let (url, connections) = counting_tracker().await;
+        let http_tracker =
+            HTTPTracker::new(generate_peer_id("-RT0001-"), HTTPTracker::default_client());
+
+        for _ in 0..2 {
+            let announce_info = http_tracker
+                .announce(&url, &[1; 20], &AnnounceRequest::default())
+                .await;
+            assert!(announce_info.is_ok());
+        }
+
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn gzip_tracker(body: &[u8]) -> wiremock::MockServer {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Encoding", "gzip")
+                    .set_body_bytes(gzip(body)),
+            )
+            .mount(&mock_server)
+            .await;
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn should_decompress_gzip_responses() {
+        let mock_server = gzip_tracker(&fs::read("tests/announce_response").unwrap()).await;
+        let http_tracker =
+            HTTPTracker::new(generate_peer_id("-RT0001-"), HTTPTracker::default_client());
+
+        let announce_info = http_tracker
+            .announce(&mock_server.uri(), &[1; 20], &AnnounceRequest::default())
+            .await;
+        assert!(announce_info.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_count_the_responses_failing_to_parse() {
+        let mock_server = wiremock::MockServer::start().await;
+        for (case, body) in [
+            ("truncated", &b"d8:intervali1800e5:peers"[..]),
+            ("malformed", b"d8:intervali1800ee"),
+            ("failure", b"d14:failure reason9:forbiddene"),
+            (
+                "valid",
+                b"d8:completei1e10:incompletei0e8:intervali1800e5:peers0:e",
+            ),
+        ] {
+            wiremock::Mock::given(wiremock::matchers::path(format!("/{}", case)))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+                .mount(&mock_server)
+                .await;
+        }
+        let counters = Arc::new(ErrorCounters::new());
+        let http_tracker =
+            HTTPTracker::new(generate_peer_id("-RT0001-"), Client::new()).metrics(counters.clone());
+
+        for (case, ok) in [
+            ("truncated", false),
+            ("malformed", false),
+            ("failure", false),
+            ("valid", true),
+        ] {
+            let url = format!("{}/{}", mock_server.uri(), case);
+            let announce_info = http_tracker
+                .announce(&url, &[1; 20], &AnnounceRequest::default())
+                .await;
+            assert_eq!(announce_info.is_ok(), ok, "{}", case);
+        }
+
+        assert_eq!(counters.get(BencodeErrorKind::Truncated), 1);
+        assert_eq!(counters.get(BencodeErrorKind::Invalid), 1);
+        assert_eq!(counters.get(BencodeErrorKind::LimitExceeded), 0);
+    }
+
+    #[tokio::test]
+    async fn should_reject_hostile_nesting_in_responses() {
+        // as deep as a response under MAX_RESPONSE_SIZE can nest
+        const DEPTH: usize = 500_000;
+        let mut body = b"d8:intervali1800e5:peers".to_vec();
+        body.extend(vec![b'l'; DEPTH]);
+        body.extend(vec![b'e'; DEPTH + 1]);
+
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+            .mount(&mock_server)
+            .await;
+        let http_tracker = HTTPTracker::new(generate_peer_id("-RT0001-"), Client::new());
+
+        let err = http_tracker
+            .announce(&mock_server.uri(), &[1; 20], &AnnounceRequest::default())
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<BencodeError>().unwrap();
+        assert_eq!(err.kind(), BencodeErrorKind::LimitExceeded);
+
+        let err = http_tracker
+            .scrape(&format!("{}/announce", mock_server.uri()), &[[1; 20]])
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("nesting exceeds the maximum depth"));
+    }
+
+    #[tokio::test]
+    async fn should_refuse_gzip_bombs() {
+        // 64 MiB of zeros compress down to a few dozen KiB
+        let mock_server = gzip_tracker(&vec![0; 64 * 1024 * 1024]).await;
+        let http_tracker =
+            HTTPTracker::new(generate_peer_id("-RT0001-"), HTTPTracker::default_client());
+
+        let err = http_tracker
+            .announce(&mock_server.uri(), &[1; 20], &AnnounceRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ResponseTooLarge>(),
+            Some(&ResponseTooLarge {
+                limit: MAX_RESPONSE_SIZE
+            })
+        );
+    }
 }
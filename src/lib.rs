@@ -1,2 +1,21 @@
+pub mod dht;
+pub mod hash;
+#[cfg(feature = "http-tracker")]
 pub mod http_tracker;
 pub mod parser;
+pub mod peer;
+pub mod peer_store;
+pub mod piece_picker;
+pub mod stats;
+pub mod storage;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod torrent_builder;
+#[cfg(feature = "http-tracker")]
+pub mod tracker;
+#[cfg(feature = "http-tracker")]
+pub mod tracker_loop;
+#[cfg(feature = "http-tracker")]
+pub mod tracker_session;
+#[cfg(feature = "udp-tracker")]
+pub mod udp_tracker;
@@ -1,4 +1,11 @@
 pub mod announce_info;
 pub mod bencode;
 pub mod byte_string;
+pub mod diff;
+pub mod json;
+pub mod magnet;
 pub mod meta_info;
+pub mod metrics;
+pub mod schema;
+pub mod scrape_info;
+pub mod visitor;
@@ -1,8 +1,44 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use indexmap::IndexMap;
+
 use crate::parser::bencode::{Bencode, BencodeError};
 use crate::parser::byte_string::ByteString;
 
-/// Response from announce tracker servers
+/// Errors returned when interpreting a tracker announce response
 #[derive(Debug, Clone)]
+pub enum AnnounceError {
+    /// The tracker rejected the request and told us why via `failure reason`
+    TrackerFailure(String),
+    /// The response is not a valid announce response
+    MalformedResponse(String),
+}
+
+impl Error for AnnounceError {}
+
+impl Display for AnnounceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnnounceError::TrackerFailure(reason) => write!(f, "tracker failure: {}", reason),
+            AnnounceError::MalformedResponse(msg) => {
+                write!(f, "malformed tracker response: {}", msg)
+            }
+        }
+    }
+}
+
+impl From<BencodeError> for AnnounceError {
+    fn from(err: BencodeError) -> Self {
+        AnnounceError::MalformedResponse(err.to_string())
+    }
+}
+
+/// Response from announce tracker servers
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AnnounceInfo {
     pub interval: u64,
     pub complete: u64,
@@ -10,73 +46,227 @@ pub struct AnnounceInfo {
     pub peers: Vec<Peer>,
     pub min_interval: Option<u64>,
     pub tracker_id: Option<String>,
+    /// `warning message`: the announce went through, but the tracker
+    /// has something to tell us (e.g. an outdated client)
+    pub warning_message: Option<String>,
 }
 
 impl AnnounceInfo {
-    pub fn parse(value: &Bencode) -> Result<Self, BencodeError> {
-        let err = |msg: &str| -> Result<Self, BencodeError> {
-            Err(BencodeError::new(format!(
+    /// Same as [`AnnounceInfo::parse`] but matches dictionary keys ignoring
+    /// their ASCII casing (e.g. `Interval` is read as `interval`).
+    /// Strict matching is what the spec mandates, so only use this
+    /// for trackers known to be sloppy with their responses.
+    pub fn parse_case_insensitive(value: &Bencode) -> Result<Self, AnnounceError> {
+        Self::parse(&lowercase_keys(value))
+    }
+
+    pub fn parse(value: &Bencode) -> Result<Self, AnnounceError> {
+        let err = |msg: &str| -> Result<Self, AnnounceError> {
+            Err(AnnounceError::MalformedResponse(format!(
                 "Invalid bencode value for AnounceInfo when decoding \"{}\": {:?}",
                 msg, value
             )))
         };
 
-        let Bencode::Dict(map) = value else {
-            return err("initial value");
-        };
+        if value.as_dict().is_none() {
+            return Err(AnnounceError::MalformedResponse(not_a_dict(value)));
+        }
 
-        let Some(Bencode::Number(complete)) = map.get(&ByteString::new("complete"))  else {
+        // Trackers omit every other key when rejecting a request,
+        // so the failure reason must be checked before anything else.
+        if let Some(reason) = value.get("failure reason") {
+            return Err(AnnounceError::TrackerFailure(match reason {
+                Bencode::Text(reason) => reason.to_string(),
+                other => format!("{:?}", other),
+            }));
+        }
+
+        let Some(complete) = value.get("complete").and_then(Bencode::as_u64) else {
             return err("complete");
         };
 
-        let Some(Bencode::Number(incomplete)) = map.get(&ByteString::new("incomplete")) else {
+        let Some(incomplete) = value.get("incomplete").and_then(Bencode::as_u64) else {
             return err("incomplete");
         };
 
-        let Some(Bencode::Number(interval)) = map.get(&ByteString::new("interval")) else {
+        let Some(interval) = value.get("interval").and_then(Bencode::as_u64) else {
             return err("interval");
         };
 
-        let Some(Bencode::List(peers_list)) =
-                                map.get(&ByteString::new("peers")) else {
-            return err("peers");
-        };
+        // BEP 7: IPv6 peers are sent separately, under `peers6`
+        let peers_value = value.get("peers");
+        let peers6_value = value.get("peers6");
+        if peers_value.is_none() && peers6_value.is_none() {
+            return Err(AnnounceError::MalformedResponse(String::from(
+                "response has neither \"peers\" nor \"failure reason\"",
+            )));
+        }
+
+        let min_interval = value.get("min interval").and_then(Bencode::as_u64);
 
-        let maybe_tracker_id = map
-            .get(&ByteString::new("tracker id"))
-            .and_then(|v| match v {
-                Bencode::Text(peer_id) => Some(peer_id.to_string()),
-                _ => None,
-            });
-        let mut peers = Vec::with_capacity(peers_list.len());
-        for peer_dict in peers_list.iter() {
-            let peer = Peer::parse(peer_dict)?;
-            peers.push(peer);
+        let warning_message = value
+            .get("warning message")
+            .and_then(Bencode::as_text)
+            .map(ByteString::to_string);
+
+        let maybe_tracker_id = value
+            .get("tracker id")
+            .and_then(Bencode::as_text)
+            .map(ByteString::to_string);
+        let mut peers = Vec::new();
+        if let Some(peers_value) = peers_value {
+            match peers_value {
+                Bencode::List(peers_list) => {
+                    for peer_dict in peers_list.iter() {
+                        let peer = Peer::parse(peer_dict)?;
+                        peers.push(peer);
+                    }
+                }
+                Bencode::Text(compact_peers) => peers.extend(Peer::parse_compact(compact_peers)?),
+                _ => return err("peers"),
+            }
+        }
+        if let Some(peers6_value) = peers6_value {
+            let Bencode::Text(compact_peers) = peers6_value else {
+                return err("peers6");
+            };
+            peers.extend(Peer::parse_compact_v6(compact_peers)?);
         }
 
         Ok(Self {
-            complete: complete.to_owned(),
-            incomplete: incomplete.to_owned(),
-            interval: interval.to_owned(),
+            complete,
+            incomplete,
+            interval,
             peers,
             tracker_id: maybe_tracker_id,
-            min_interval: None,
+            min_interval,
+            warning_message,
         })
     }
 }
 
-#[derive(Debug, Clone)]
+impl AnnounceInfo {
+    /// Build the tracker response this was parsed from,
+    /// with peers in the dictionary model.
+    pub fn to_bencode(&self) -> Bencode {
+        let mut map = IndexMap::new();
+        map.insert(
+            ByteString::new("complete"),
+            Bencode::Number(self.complete as i64),
+        );
+        map.insert(
+            ByteString::new("incomplete"),
+            Bencode::Number(self.incomplete as i64),
+        );
+        map.insert(
+            ByteString::new("interval"),
+            Bencode::Number(self.interval as i64),
+        );
+        if let Some(min_interval) = self.min_interval {
+            map.insert(
+                ByteString::new("min interval"),
+                Bencode::Number(min_interval as i64),
+            );
+        }
+        map.insert(
+            ByteString::new("peers"),
+            Bencode::List(self.peers.iter().map(Peer::to_bencode).collect()),
+        );
+        if let Some(tracker_id) = &self.tracker_id {
+            map.insert(
+                ByteString::new("tracker id"),
+                Bencode::Text(ByteString::new(tracker_id)),
+            );
+        }
+        if let Some(warning_message) = &self.warning_message {
+            map.insert(
+                ByteString::new("warning message"),
+                Bencode::Text(ByteString::new(warning_message)),
+            );
+        }
+        Bencode::Dict(map)
+    }
+}
+
+/// Longest preview of an unexpected response shown in errors
+const PREVIEW_LEN: usize = 64;
+
+/// Describe a response which isn't a dict, as a proxy error page
+/// or a misbehaving tracker could send, with a short preview of it.
+fn not_a_dict(value: &Bencode) -> String {
+    let kind = match value {
+        Bencode::Text(_) => "Text",
+        Bencode::Number(_) => "Number",
+        Bencode::List(_) => "List",
+        Bencode::Dict(_) => "Dict",
+    };
+    let mut preview = format!("{:?}", value);
+    if let Some((end, _)) = preview.char_indices().nth(PREVIEW_LEN) {
+        preview.truncate(end);
+        preview.push_str("...");
+    }
+    format!(
+        "expected a Dict at the top level, got a {}: {}",
+        kind, preview
+    )
+}
+
+/// Split compact peers into records, skipping the ones already seen
+fn unique_records(value: &[u8], record_len: usize) -> impl Iterator<Item = &[u8]> {
+    let mut seen = HashSet::new();
+    value
+        .chunks_exact(record_len)
+        .filter(move |record| seen.insert(*record))
+}
+
+/// Peers are ordered by address, so lists of peers collected from different
+/// sources (compact or dictionary model, DHT...) can be compared once sorted.
+impl Ord for Peer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Peer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Recursively lowercase (ASCII only) all dictionary keys of the given value
+fn lowercase_keys(value: &Bencode) -> Bencode {
+    match value {
+        Bencode::Dict(map) => Bencode::Dict(
+            map.iter()
+                .map(|(key, value)| {
+                    (
+                        ByteString::from_vec(key.to_ascii_lowercase()),
+                        lowercase_keys(value),
+                    )
+                })
+                .collect(),
+        ),
+        Bencode::List(list) => Bencode::List(list.iter().map(lowercase_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Peer {
+    /// empty when not sent by the tracker, always the case in the compact model
     pub peer_id: String,
     pub ip: String,
     pub port: u64,
 }
 
 impl Peer {
-    // TODO: Must handle peers in the binary model format as well.
-    // The peers value may be a string consisting of multiples of 6 bytes.
-    // First 4 bytes are the IP address and last 2 bytes are the port number,
-    // all in network (big endian) notation.
+    /// Key peers are sorted by: IP addresses compare numerically (IPv4 before IPv6),
+    /// then the port. Trackers may also send hostnames, which come first.
+    fn sort_key(&self) -> (Option<IpAddr>, &str, u64, &str) {
+        (self.ip.parse().ok(), &self.ip, self.port, &self.peer_id)
+    }
+
+    /// Parse a peer in the dictionary model
     pub fn parse(value: &Bencode) -> Result<Self, BencodeError> {
         let err = |msg: &str| -> Result<Self, BencodeError> {
             Err(BencodeError::new(format!(
@@ -88,22 +278,365 @@ impl Peer {
             return err("raw value");
         };
 
-        let Some(Bencode::Text(peer_id)) = map.get(&ByteString::new("peer id")) else {
-            return err("peer id");
+        // trackers hiding peer ids leave it out, as in the compact model
+        let peer_id = match map.get(&ByteString::new("peer id")) {
+            Some(Bencode::Text(peer_id)) => peer_id.to_string(),
+            None => String::new(),
+            Some(_) => return err("peer id"),
         };
 
         let Some(Bencode::Text(ip)) = map.get(&ByteString::new("ip")) else {
             return err("ip");
         };
 
-        let Some(Bencode::Number(port)) = map.get(&ByteString::new("port"))  else {
+        let Some(port) = map.get(&ByteString::new("port")).and_then(Bencode::as_u64) else {
             return err("port");
         };
 
         Ok(Self {
-            peer_id: peer_id.to_string(),
+            peer_id,
             ip: ip.to_string(),
-            port: port.to_owned(),
+            port,
         })
     }
+
+    /// Build the peer dict of the dictionary model
+    pub fn to_bencode(&self) -> Bencode {
+        Bencode::Dict(IndexMap::from([
+            (
+                ByteString::new("ip"),
+                Bencode::Text(ByteString::new(&self.ip)),
+            ),
+            (
+                ByteString::new("peer id"),
+                Bencode::Text(ByteString::new(&self.peer_id)),
+            ),
+            (ByteString::new("port"), Bencode::Number(self.port as i64)),
+        ]))
+    }
+
+    /// Parse IPv4 peers in the compact format (BEP 23): a string consisting
+    /// of multiples of 6 bytes. First 4 bytes are the IP address and
+    /// last 2 bytes are the port number, all in network (big endian) notation.
+    /// Duplicate records, which some trackers send, are only returned once.
+    pub fn parse_compact(value: &ByteString) -> Result<Vec<Self>, BencodeError> {
+        const RECORD_LEN: usize = 6;
+        if !value.len().is_multiple_of(RECORD_LEN) {
+            return Err(BencodeError::new(format!(
+                "Invalid compact peers length {}, must be a multiple of {}",
+                value.len(),
+                RECORD_LEN
+            )));
+        }
+
+        Ok(unique_records(value, RECORD_LEN)
+            .map(|record| Self {
+                peer_id: String::new(),
+                ip: Ipv4Addr::new(record[0], record[1], record[2], record[3]).to_string(),
+                port: u16::from_be_bytes([record[4], record[5]]) as u64,
+            })
+            .collect())
+    }
+
+    /// Parse IPv6 peers in the compact format (BEP 7): a string consisting
+    /// of multiples of 18 bytes. First 16 bytes are the IP address and
+    /// last 2 bytes are the port number, all in network (big endian) notation.
+    /// Duplicate records are only returned once.
+    pub fn parse_compact_v6(value: &ByteString) -> Result<Vec<Self>, BencodeError> {
+        const RECORD_LEN: usize = 18;
+        if !value.len().is_multiple_of(RECORD_LEN) {
+            return Err(BencodeError::new(format!(
+                "Invalid compact IPv6 peers length {}, must be a multiple of {}",
+                value.len(),
+                RECORD_LEN
+            )));
+        }
+
+        Ok(unique_records(value, RECORD_LEN)
+            .map(|record| {
+                let mut ip = [0; 16];
+                ip.copy_from_slice(&record[..16]);
+                Self {
+                    peer_id: String::new(),
+                    ip: Ipv6Addr::from(ip).to_string(),
+                    port: u16::from_be_bytes([record[16], record[17]]) as u64,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::bencode::BencodeParser;
+
+    use super::*;
+
+    #[test]
+    fn should_report_tracker_failure() {
+        let value = BencodeParser::decode(b"d14:failure reason17:torrent not founde").unwrap();
+        let result = AnnounceInfo::parse(&value);
+
+        let Err(AnnounceError::TrackerFailure(reason)) = &result else {
+            panic!("expected a tracker failure, got {:?}", result);
+        };
+        assert_eq!(reason, "torrent not found");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "tracker failure: torrent not found"
+        );
+    }
+
+    #[test]
+    fn should_parse_the_min_interval_when_present() {
+        let value = BencodeParser::decode(
+            b"d8:completei1e10:incompletei0e8:intervali1800e12:min intervali900e5:peerslee",
+        )
+        .unwrap();
+        assert_eq!(AnnounceInfo::parse(&value).unwrap().min_interval, Some(900));
+
+        let value =
+            BencodeParser::decode(b"d8:completei1e10:incompletei0e8:intervali1800e5:peerslee")
+                .unwrap();
+        assert_eq!(AnnounceInfo::parse(&value).unwrap().min_interval, None);
+    }
+
+    #[test]
+    fn should_report_responses_which_are_not_dicts() {
+        let value = BencodeParser::decode(b"l8:intervali1800e5:peersl5:abcdeee").unwrap();
+        let Err(AnnounceError::MalformedResponse(msg)) = AnnounceInfo::parse(&value) else {
+            panic!("expected a malformed response");
+        };
+        assert_eq!(
+            msg,
+            "expected a Dict at the top level, got a List: List([Text(interval), Number(1800), Text(peers), List([Text(abcd..."
+        );
+
+        let value = BencodeParser::decode(b"i42e").unwrap();
+        let err = AnnounceInfo::parse(&value).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("expected a Dict at the top level, got a Number: Number(42)"));
+    }
+
+    #[test]
+    fn should_keep_the_warning_message() {
+        let value = BencodeParser::decode(
+            b"d8:completei1e10:incompletei0e8:intervali1800e5:peersle15:warning message18:client is outdatede",
+        )
+        .unwrap();
+
+        let announce_info = AnnounceInfo::parse(&value).unwrap();
+        assert_eq!(
+            announce_info.warning_message.as_deref(),
+            Some("client is outdated")
+        );
+    }
+
+    #[test]
+    fn should_report_malformed_response_without_peers() {
+        let value =
+            BencodeParser::decode(b"d8:completei1e10:incompletei2e8:intervali1800ee").unwrap();
+        let result = AnnounceInfo::parse(&value);
+
+        let Err(AnnounceError::MalformedResponse(msg)) = result else {
+            panic!("expected a malformed response, got {:?}", result);
+        };
+        assert!(msg.contains("neither \"peers\" nor \"failure reason\""));
+    }
+
+    #[test]
+    fn should_parse_dictionary_peers_without_peer_id() {
+        let value = BencodeParser::decode(
+            b"d8:completei1e10:incompletei2e8:intervali1800e5:peersld2:ip9:127.0.0.14:porti6881eeee",
+        )
+        .unwrap();
+
+        let announce_info = AnnounceInfo::parse(&value).unwrap();
+        assert_eq!(
+            announce_info.peers,
+            vec![Peer {
+                peer_id: String::new(),
+                ip: String::from("127.0.0.1"),
+                port: 6881,
+            }]
+        );
+
+        let value = BencodeParser::decode(b"d7:peer idi1e2:ip9:127.0.0.14:porti6881ee").unwrap();
+        assert!(Peer::parse(&value).is_err());
+    }
+
+    #[test]
+    fn should_match_keys_ignoring_case_in_lenient_mode() {
+        let value = BencodeParser::decode(
+            b"d8:Completei1e10:incompletei2e8:Intervali1800e5:peersld2:IP9:127.0.0.17:peer id3:abc4:porti6881eeee",
+        )
+        .unwrap();
+
+        assert!(AnnounceInfo::parse(&value).is_err());
+
+        let announce_info = AnnounceInfo::parse_case_insensitive(&value).unwrap();
+        assert_eq!(announce_info.interval, 1800);
+        assert_eq!(announce_info.complete, 1);
+        assert_eq!(announce_info.peers[0].ip, "127.0.0.1");
+    }
+
+    #[test]
+    fn should_merge_ipv4_and_ipv6_peers() {
+        let mut raw =
+            b"d8:completei1e10:incompletei2e8:intervali1800e5:peersld2:ip9:127.0.0.17:peer id3:abc4:porti6881eee6:peers636:"
+                .to_vec();
+        raw.extend(Ipv6Addr::LOCALHOST.octets());
+        raw.extend(6882u16.to_be_bytes());
+        raw.extend("2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        raw.extend(6883u16.to_be_bytes());
+        raw.push(b'e');
+
+        let announce_info = AnnounceInfo::parse(&BencodeParser::decode(&raw).unwrap()).unwrap();
+        let addresses: Vec<(&str, u64)> = announce_info
+            .peers
+            .iter()
+            .map(|peer| (peer.ip.as_str(), peer.port))
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![("127.0.0.1", 6881), ("::1", 6882), ("2001:db8::1", 6883)]
+        );
+    }
+
+    #[test]
+    fn should_accept_responses_with_only_ipv6_peers() {
+        let mut raw = b"d8:completei1e10:incompletei2e8:intervali1800e6:peers636:".to_vec();
+        raw.extend("fe80::1:2".parse::<Ipv6Addr>().unwrap().octets());
+        raw.extend(51413u16.to_be_bytes());
+        raw.extend("2001:db8:0:0:1::".parse::<Ipv6Addr>().unwrap().octets());
+        raw.extend(6881u16.to_be_bytes());
+        raw.push(b'e');
+
+        let announce_info = AnnounceInfo::parse(&BencodeParser::decode(&raw).unwrap()).unwrap();
+        let addresses: Vec<(&str, u64)> = announce_info
+            .peers
+            .iter()
+            .map(|peer| (peer.ip.as_str(), peer.port))
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![("fe80::1:2", 51413), ("2001:db8:0:0:1::", 6881)]
+        );
+    }
+
+    #[test]
+    fn should_reject_truncated_compact_ipv6_peers() {
+        let value = BencodeParser::decode(
+            b"d8:completei1e10:incompletei2e8:intervali1800e6:peers65:abcdee",
+        )
+        .unwrap();
+        assert!(matches!(
+            AnnounceInfo::parse(&value),
+            Err(AnnounceError::MalformedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn should_round_trip_through_bencode() {
+        let announce_info = AnnounceInfo {
+            interval: 1800,
+            complete: 5,
+            incomplete: 2,
+            peers: vec![
+                Peer {
+                    peer_id: String::from("-RT0001-abcdefghijkl"),
+                    ip: String::from("10.0.0.1"),
+                    port: 6881,
+                },
+                Peer {
+                    peer_id: String::from("-RT0001-mnopqrstuvwx"),
+                    ip: String::from("::1"),
+                    port: 51413,
+                },
+            ],
+            min_interval: None,
+            tracker_id: Some(String::from("tracker-42")),
+            warning_message: Some(String::from("client is outdated")),
+        };
+
+        let encoded = BencodeParser::encode(&announce_info.to_bencode());
+        let decoded = BencodeParser::decode(&encoded).unwrap();
+        assert_eq!(AnnounceInfo::parse(&decoded).unwrap(), announce_info);
+    }
+
+    #[test]
+    fn should_sort_peers_by_address() {
+        let peer = |ip: &str, port: u64| Peer {
+            peer_id: String::new(),
+            ip: String::from(ip),
+            port,
+        };
+        let mut peers = [
+            peer("::1", 6881),
+            peer("10.0.0.10", 6881),
+            peer("10.0.0.2", 6882),
+            peer("10.0.0.2", 6881),
+            peer("tracker.example.org", 6881),
+        ];
+        peers.sort();
+
+        let addresses: Vec<(&str, u64)> = peers
+            .iter()
+            .map(|peer| (peer.ip.as_str(), peer.port))
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                ("tracker.example.org", 6881),
+                ("10.0.0.2", 6881),
+                ("10.0.0.2", 6882),
+                ("10.0.0.10", 6881),
+                ("::1", 6881)
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_compact_ipv4_peers() {
+        let mut raw = b"d8:completei1e10:incompletei2e8:intervali1800e5:peers12:".to_vec();
+        raw.extend([192, 168, 1, 20, 0xc8, 0xd5]);
+        raw.extend([203, 0, 113, 7, 0x1a, 0xe9]);
+        raw.push(b'e');
+
+        let announce_info = AnnounceInfo::parse(&BencodeParser::decode(&raw).unwrap()).unwrap();
+        assert_eq!(
+            announce_info.peers,
+            vec![
+                Peer {
+                    peer_id: String::new(),
+                    ip: String::from("192.168.1.20"),
+                    port: 51413,
+                },
+                Peer {
+                    peer_id: String::new(),
+                    ip: String::from("203.0.113.7"),
+                    port: 6889,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_deduplicate_compact_peers_within_a_response() {
+        let mut raw = b"d8:completei1e10:incompletei2e8:intervali1800e5:peers18:".to_vec();
+        raw.extend([10, 0, 0, 1, 0x1a, 0xe1]);
+        raw.extend([10, 0, 0, 2, 0x1a, 0xe1]);
+        raw.extend([10, 0, 0, 1, 0x1a, 0xe1]);
+        raw.push(b'e');
+
+        let announce_info = AnnounceInfo::parse(&BencodeParser::decode(&raw).unwrap()).unwrap();
+        let addresses: Vec<(&str, u64)> = announce_info
+            .peers
+            .iter()
+            .map(|peer| (peer.ip.as_str(), peer.port))
+            .collect();
+        assert_eq!(addresses, vec![("10.0.0.1", 6881), ("10.0.0.2", 6881)]);
+    }
 }
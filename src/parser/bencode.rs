@@ -2,53 +2,774 @@ use indexmap::IndexMap;
 
 use crate::parser::byte_string::ByteString;
 use std::error::Error;
-use std::{fmt::Display, fs};
+use std::fmt::{Debug, Display};
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone)]
 pub enum Bencode {
     // Bencode text is always represented as byte strings
     Text(ByteString),
-    Number(u64),
+    Number(i64),
     List(Vec<Bencode>),
     Dict(IndexMap<ByteString, Bencode>),
 }
 
+impl Bencode {
+    /// Recursively sort the keys of every dictionary in this value
+    /// by their raw bytes, which is the canonical order required by the spec,
+    /// so iterating over the dicts follows the same order as their encoding.
+    pub fn canonicalize(&mut self) {
+        match self {
+            Bencode::Dict(map) => {
+                map.sort_by(|k1, _, k2, _| k1.0.cmp(&k2.0));
+                for value in map.values_mut() {
+                    value.canonicalize();
+                }
+            }
+            Bencode::List(list) => {
+                for value in list.iter_mut() {
+                    value.canonicalize();
+                }
+            }
+            Bencode::Text(_) | Bencode::Number(_) => {}
+        }
+    }
+
+    /// The number if this is a non-negative one, for the many fields
+    /// (lengths, ports, intervals...) that can't be negative.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Bencode::Number(number) => u64::try_from(*number).ok(),
+            _ => None,
+        }
+    }
+
+    /// The number, which may be negative, see [`Bencode::as_u64`] otherwise
+    pub fn as_number(&self) -> Option<i64> {
+        match self {
+            Bencode::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&ByteString> {
+        match self {
+            Bencode::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Bencode]> {
+        match self {
+            Bencode::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&IndexMap<ByteString, Bencode>> {
+        match self {
+            Bencode::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Value stored under `key`, if this is a dict holding that key
+    pub fn get(&self, key: &str) -> Option<&Bencode> {
+        self.as_dict()?.get(&ByteString::new(key))
+    }
+}
+
+/// Longest text values shown by the [`Debug`] output of [`Bencode`]
+const DEBUG_MAX_TEXT_LEN: usize = 64;
+/// Items shown per list or dict by the [`Debug`] output of [`Bencode`]
+const DEBUG_MAX_ITEMS: usize = 16;
+/// Deepest level shown by the [`Debug`] output of [`Bencode`]
+const DEBUG_MAX_DEPTH: usize = 16;
+
+/// Torrents hold megabytes of piece hashes and sometimes thousands of files,
+/// so long texts, lists and dicts are elided past a few items (showing how
+/// many there are) to keep test failures and logs readable.
+impl Debug for Bencode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        BoundedDebug(self, 0).fmt(f)
+    }
+}
+
+/// A value along with its depth, to elide the contents past [`DEBUG_MAX_DEPTH`]
+struct BoundedDebug<'a>(&'a Bencode, usize);
+
+impl Debug for BoundedDebug<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let BoundedDebug(value, depth) = *self;
+        match value {
+            Bencode::Text(text) if text.len() > DEBUG_MAX_TEXT_LEN => {
+                write!(f, "Text(<{} bytes>)", text.len())
+            }
+            Bencode::Text(text) => f.debug_tuple("Text").field(text).finish(),
+            Bencode::Number(number) => f.debug_tuple("Number").field(number).finish(),
+            Bencode::List(list) if depth >= DEBUG_MAX_DEPTH => {
+                write!(f, "List(<{} items>)", list.len())
+            }
+            Bencode::Dict(dict) if depth >= DEBUG_MAX_DEPTH => {
+                write!(f, "Dict(<{} entries>)", dict.len())
+            }
+            Bencode::List(list) => f
+                .debug_tuple("List")
+                .field(&BoundedItems(list, depth))
+                .finish(),
+            Bencode::Dict(dict) => f
+                .debug_tuple("Dict")
+                .field(&BoundedEntries(dict, depth))
+                .finish(),
+        }
+    }
+}
+
+struct BoundedItems<'a>(&'a [Bencode], usize);
+
+impl Debug for BoundedItems<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let BoundedItems(list, depth) = *self;
+        let mut debug = f.debug_list();
+        debug.entries(
+            list.iter()
+                .take(DEBUG_MAX_ITEMS)
+                .map(|item| BoundedDebug(item, depth + 1)),
+        );
+        if list.len() > DEBUG_MAX_ITEMS {
+            debug.entry(&format_args!("...{} more", list.len() - DEBUG_MAX_ITEMS));
+        }
+        debug.finish()
+    }
+}
+
+struct BoundedEntries<'a>(&'a IndexMap<ByteString, Bencode>, usize);
+
+impl Debug for BoundedEntries<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let BoundedEntries(dict, depth) = *self;
+        let mut debug = f.debug_map();
+        debug.entries(
+            dict.iter()
+                .take(DEBUG_MAX_ITEMS)
+                .map(|(key, value)| (key, BoundedDebug(value, depth + 1))),
+        );
+        if dict.len() > DEBUG_MAX_ITEMS {
+            debug.key(&format_args!("...{} more", dict.len() - DEBUG_MAX_ITEMS));
+            debug.value(&format_args!("..."));
+        }
+        debug.finish()
+    }
+}
+
+/// Broad category of a [`BencodeError`], e.g. to count failures by cause
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BencodeErrorKind {
+    /// The content isn't valid bencode or doesn't have the expected structure
+    Invalid,
+    /// The content ends in the middle of a value
+    Truncated,
+    /// One of the decoding limits (size, depth...) was exceeded
+    LimitExceeded,
+    /// The content couldn't be read
+    Io,
+}
+
 #[derive(Debug, Clone)]
 pub struct BencodeError {
+    kind: BencodeErrorKind,
     message: String,
+    /// offset of the byte at which the error was detected
+    position: Option<usize>,
+    /// underlying cause, e.g. the IO error that prevented reading a file
+    source: Option<Arc<dyn Error + Send + Sync>>,
 }
 
 impl BencodeError {
     pub fn new<M: Into<String>>(message: M) -> Self {
+        Self::with_kind(BencodeErrorKind::Invalid, message)
+    }
+
+    pub fn with_kind<M: Into<String>>(kind: BencodeErrorKind, message: M) -> Self {
         Self {
+            kind,
             message: message.into(),
+            position: None,
+            source: None,
+        }
+    }
+
+    pub fn kind(&self) -> BencodeErrorKind {
+        self.kind
+    }
+
+    /// Offset in the decoded content of the byte at which the error was
+    /// detected, if known. For truncated content, that's its length.
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    /// Set the offset of the offending byte, unless already known
+    pub fn at(mut self, position: usize) -> Self {
+        self.position.get_or_insert(position);
+        self
+    }
+
+    /// Create an error caused by another one, which is then
+    /// exposed via [`Error::source`]
+    pub fn with_source<M, E>(message: M, source: E) -> Self
+    where
+        M: Into<String>,
+        E: Error + Send + Sync + 'static,
+    {
+        Self {
+            kind: BencodeErrorKind::Io,
+            message: message.into(),
+            position: None,
+            source: Some(Arc::new(source)),
         }
     }
 }
 
-impl Error for BencodeError {}
+impl Error for BencodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
 
 impl Display for BencodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+        match self.position {
+            Some(position) => write!(f, "{} at byte {}", self.message, position),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Largest file accepted by [`BencodeParser::from_file`] and friends.
+/// Torrent files rarely go over a few MB.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Limits applied while decoding untrusted data (tracker responses,
+/// torrents from unknown sources...), so a malicious input fails
+/// with an error instead of exhausting the stack or memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Maximum nesting of lists and dicts
+    pub max_depth: usize,
+    /// Maximum length of the whole raw content
+    pub max_total_bytes: usize,
+    /// Maximum number of entries in a single list or dict
+    pub max_collection_len: usize,
+}
+
+impl DecodeOptions {
+    /// No limits at all, only meant for trusted content: dropping, cloning
+    /// or comparing values nested too deeply overflows the stack.
+    pub fn unlimited() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_total_bytes: usize::MAX,
+            max_collection_len: usize::MAX,
+        }
+    }
+}
+
+/// Defaults are generous enough for tracker responses and torrent files.
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 100,
+            max_total_bytes: DEFAULT_MAX_FILE_SIZE as usize,
+            max_collection_len: 100_000,
+        }
+    }
+}
+
+/// List or dict being parsed
+enum Container {
+    List(Vec<Bencode>),
+    /// The key is set once read, until its value is parsed
+    Dict(IndexMap<ByteString, Bencode>, Option<ByteString>),
+}
+
+impl Container {
+    fn name(&self) -> &'static str {
+        match self {
+            Container::List(_) => "list",
+            Container::Dict(..) => "dict",
+        }
+    }
+
+    fn into_value(self) -> Bencode {
+        match self {
+            Container::List(items) => Bencode::List(items),
+            Container::Dict(map, _) => Bencode::Dict(map),
+        }
+    }
+}
+
+/// Bytes being decoded, either in memory or read from a stream
+trait ByteSource {
+    fn next_byte(&mut self) -> Result<Option<u8>, BencodeError>;
+
+    /// Read the `len` bytes of a string value
+    fn read_string(&mut self, len: u64) -> Result<Vec<u8>, BencodeError>;
+}
+
+impl ByteSource for std::slice::Iter<'_, u8> {
+    fn next_byte(&mut self) -> Result<Option<u8>, BencodeError> {
+        Ok(self.next().copied())
+    }
+
+    fn read_string(&mut self, len: u64) -> Result<Vec<u8>, BencodeError> {
+        // Checked up front so a truncated input gets a clear error
+        // instead of silently yielding a shorter string.
+        let remaining = self.len();
+        if len > remaining as u64 {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::Truncated,
+                format!(
+                    "string length {} exceeds remaining {} bytes",
+                    len, remaining
+                ),
+            ));
+        }
+        let (string, rest) = self.as_slice().split_at(len as usize);
+        *self = rest.iter();
+        Ok(string.to_vec())
+    }
+}
+
+/// Reads a stream byte by byte, so nothing past the decoded value is consumed
+struct ReaderSource<R> {
+    reader: R,
+    /// number of bytes read so far
+    position: usize,
+    /// bytes that may be read in total, see [`DecodeOptions::max_total_bytes`]
+    max_bytes: usize,
+}
+
+impl<R> ReaderSource<R> {
+    /// Fail if reading `len` more bytes would go over the maximum
+    fn reserve(&self, len: u64) -> Result<(), BencodeError> {
+        if len > (self.max_bytes - self.position) as u64 {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::LimitExceeded,
+                format!("stream exceeds the maximum of {} bytes", self.max_bytes),
+            )
+            .at(self.position));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> ByteSource for ReaderSource<R> {
+    fn next_byte(&mut self) -> Result<Option<u8>, BencodeError> {
+        self.reserve(1)?;
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.position += 1;
+                    return Ok(Some(byte[0]));
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    return Err(BencodeError::with_source(
+                        "could not read bencode content",
+                        err,
+                    ))
+                }
+            }
+        }
+    }
+
+    fn read_string(&mut self, len: u64) -> Result<Vec<u8>, BencodeError> {
+        self.reserve(len)?;
+        // grows with the bytes actually read rather than the announced length
+        let mut string = Vec::new();
+        (&mut self.reader)
+            .take(len)
+            .read_to_end(&mut string)
+            .map_err(|err| BencodeError::with_source("could not read bencode content", err))?;
+        self.position += string.len();
+        if (string.len() as u64) < len {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::Truncated,
+                format!(
+                    "stream ended after {} of the {} bytes of a string",
+                    string.len(),
+                    len
+                ),
+            ));
+        }
+        Ok(string)
     }
 }
 
 pub struct BencodeParser;
 
 impl BencodeParser {
-    /// Parse the given raw content to a Bencode value
+    /// Parse the given raw content to a Bencode value,
+    /// within the [`DecodeOptions::default`] limits.
     pub fn decode(raw_content: &[u8]) -> Result<Bencode, BencodeError> {
+        Self::decode_with_options(raw_content, &DecodeOptions::default())
+    }
+
+    /// Parse the given raw content to a Bencode value,
+    /// failing as soon as one of the given limits is exceeded.
+    pub fn decode_with_options(
+        raw_content: &[u8],
+        options: &DecodeOptions,
+    ) -> Result<Bencode, BencodeError> {
+        if raw_content.len() > options.max_total_bytes {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::LimitExceeded,
+                format!(
+                    "content of {} bytes exceeds the maximum of {} bytes",
+                    raw_content.len(),
+                    options.max_total_bytes
+                ),
+            ));
+        }
+        Self::check_not_blank(raw_content)?;
         let mut iterator = raw_content.iter();
-        Self::parse(&mut iterator)
+        Self::parse(&mut iterator, options, 0)
+            .map_err(|err| Self::locate(err, raw_content, iterator.len()))
     }
 
+    /// Parse the given raw content to a Bencode value, rejecting
+    /// any bytes left over after the first complete value.
+    pub fn decode_strict(raw_content: &[u8]) -> Result<Bencode, BencodeError> {
+        Self::check_not_blank(raw_content)?;
+        let mut iterator = raw_content.iter();
+        let value = Self::parse(&mut iterator, &DecodeOptions::default(), 0)
+            .map_err(|err| Self::locate(err, raw_content, iterator.len()))?;
+        let trailing = iterator.len();
+        if trailing > 0 {
+            return Err(BencodeError::new(format!(
+                "Unexpected {} trailing bytes after bencode value",
+                trailing
+            ))
+            .at(raw_content.len() - trailing));
+        }
+        Ok(value)
+    }
+
+    /// Reject content that obviously isn't bencode (empty, only whitespace
+    /// or control bytes) with a clear error, rather than one about its first byte
+    fn check_not_blank(raw_content: &[u8]) -> Result<(), BencodeError> {
+        if raw_content.is_empty() {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::Truncated,
+                "empty content is not bencode",
+            ));
+        }
+        if raw_content
+            .iter()
+            .all(|byte| byte.is_ascii_whitespace() || byte.is_ascii_control())
+        {
+            return Err(BencodeError::new(format!(
+                "content of {} bytes is not bencode: only whitespace and control bytes",
+                raw_content.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Parse a single value from a stream (socket, file...), reading it
+    /// incrementally instead of buffering the whole content first.
+    /// Nothing past the end of the value is read.
+    ///
+    /// Bytes are read one at a time, so unbuffered readers such as
+    /// a [`File`] should be wrapped in a [`std::io::BufReader`].
+    ///
+    /// The stream is untrusted, so the [`DecodeOptions::default`] limits apply.
+    pub fn decode_from<R: Read>(reader: R) -> Result<Bencode, BencodeError> {
+        Self::decode_from_with_options(reader, &DecodeOptions::default())
+    }
+
+    /// Same as [`BencodeParser::decode_from`], failing as soon as one of
+    /// the given limits is exceeded. `max_total_bytes` bounds the bytes read
+    /// from the stream, so a huge announced string length fails up front.
+    pub fn decode_from_with_options<R: Read>(
+        reader: R,
+        options: &DecodeOptions,
+    ) -> Result<Bencode, BencodeError> {
+        let mut source = ReaderSource {
+            reader,
+            position: 0,
+            max_bytes: options.max_total_bytes,
+        };
+        Self::parse(&mut source, options, 0).map_err(|err| {
+            let position = match err.kind() {
+                BencodeErrorKind::Truncated | BencodeErrorKind::Io => source.position,
+                _ => source.position.saturating_sub(1),
+            };
+            err.at(position)
+        })
+    }
+
+    /// Parse the given raw content as far as possible. On error, the error is
+    /// returned along with the value built up to that point: lists and dicts
+    /// left open are closed and the value being parsed is dropped.
+    /// Handy to see how much of a truncated or corrupt torrent is intact.
+    pub fn decode_lenient(raw_content: &[u8]) -> (Option<Bencode>, Option<BencodeError>) {
+        let mut iterator = raw_content.iter();
+        let mut stack = Vec::new();
+        match Self::parse_with_stack(&mut iterator, &DecodeOptions::default(), 0, &mut stack) {
+            Ok(value) => (Some(value), None),
+            Err(err) => {
+                let err = Self::locate(err, raw_content, iterator.len());
+                (Self::close_containers(stack), Some(err))
+            }
+        }
+    }
+
+    /// Close the lists and dicts left open by an error, innermost first
+    fn close_containers(mut stack: Vec<Container>) -> Option<Bencode> {
+        let mut value = None;
+        while let Some(mut container) = stack.pop() {
+            if let Some(child) = value.take() {
+                match &mut container {
+                    Container::List(items) => items.push(child),
+                    Container::Dict(map, key) => {
+                        let key = key.take().expect("dict values always follow a key");
+                        map.insert(key, child);
+                    }
+                }
+            }
+            value = Some(container.into_value());
+        }
+        value
+    }
+
+    /// Parse the leading top-level dictionary of the given raw content,
+    /// also returning the byte range each of its values occupies in `raw_content`.
+    ///
+    /// This lets callers get hold of the exact original bytes of a value
+    /// (e.g. the `info` dict for the info_hash) without re-encoding it.
+    /// Values are decoded within the [`DecodeOptions::default`] limits.
+    pub fn decode_dict_with_spans(
+        raw_content: &[u8],
+    ) -> Result<(Bencode, IndexMap<ByteString, Range<usize>>), BencodeError> {
+        let mut iterator = raw_content.iter();
+        Self::parse_dict_with_spans(raw_content, &mut iterator)
+            .map_err(|err| Self::locate(err, raw_content, iterator.len()))
+    }
+
+    fn parse_dict_with_spans(
+        raw_content: &[u8],
+        iterator: &mut std::slice::Iter<u8>,
+    ) -> Result<(Bencode, IndexMap<ByteString, Range<usize>>), BencodeError> {
+        let offset = |iterator: &std::slice::Iter<u8>| raw_content.len() - iterator.len();
+
+        let Some(b'd') = iterator.next() else {
+            return Err(BencodeError::new("Expected a bencode dictionary"));
+        };
+
+        let mut map = IndexMap::new();
+        let mut spans = IndexMap::new();
+        loop {
+            match iterator.next().map(|&byte| byte as char) {
+                Some(c) if Self::is_digit(c) => {
+                    let key = Self::parse_str(c, iterator)?;
+                    let start = offset(iterator);
+                    let value = Self::parse(iterator, &DecodeOptions::default(), 1)?;
+                    spans.insert(key.clone(), start..offset(iterator));
+                    map.insert(key, value);
+                }
+                Some('e') => break,
+                Some(c) => {
+                    return Err(BencodeError::new(format!(
+                        "Invalid string byte for dict length '{}'",
+                        c
+                    )))
+                }
+                None => return Err(Self::unterminated("dict")),
+            }
+        }
+
+        Ok((Bencode::Dict(map), spans))
+    }
+
+    /// Find the byte range of the value stored under `key` in the leading
+    /// top-level dictionary of the given raw content.
+    ///
+    /// Values are only scanned over, not decoded, so this is much cheaper
+    /// than [`BencodeParser::decode`] when a single entry is needed.
+    pub fn find_dict_value_span(
+        raw_content: &[u8],
+        key: &[u8],
+    ) -> Result<Option<Range<usize>>, BencodeError> {
+        let Some(b'd') = raw_content.first() else {
+            return Err(BencodeError::new("Expected a bencode dictionary").at(0));
+        };
+
+        let mut pos = 1;
+        loop {
+            match raw_content.get(pos) {
+                Some(b'e') => return Ok(None),
+                Some(c) if c.is_ascii_digit() => {
+                    let key_span = Self::scan_str(raw_content, pos)?;
+                    let value_start = key_span.end;
+                    let value_end = Self::scan_value(raw_content, value_start)?;
+                    if &raw_content[key_span] == key {
+                        return Ok(Some(value_start..value_end));
+                    }
+                    pos = value_end;
+                }
+                Some(&c) => {
+                    return Err(BencodeError::new(format!(
+                        "Invalid string byte for dict length '{}'",
+                        c as char
+                    ))
+                    .at(pos))
+                }
+                None => return Err(Self::unterminated("dict").at(pos)),
+            }
+        }
+    }
+
+    /// Return the position right after the value starting at `pos`
+    fn scan_value(raw_content: &[u8], mut pos: usize) -> Result<usize, BencodeError> {
+        // lists and dicts opened but not closed yet
+        let mut open = 0usize;
+        loop {
+            match raw_content.get(pos) {
+                Some(b'i') => match raw_content[pos..].iter().position(|&byte| byte == b'e') {
+                    Some(len) => pos += len + 1,
+                    None => {
+                        return Err(BencodeError::with_kind(
+                            BencodeErrorKind::Truncated,
+                            "Unterminated integer value",
+                        )
+                        .at(raw_content.len()))
+                    }
+                },
+                Some(b'l') | Some(b'd') => {
+                    open += 1;
+                    pos += 1;
+                }
+                Some(b'e') if open > 0 => {
+                    open -= 1;
+                    pos += 1;
+                }
+                Some(c) if c.is_ascii_digit() => pos = Self::scan_str(raw_content, pos)?.end,
+                Some(&c) => {
+                    return Err(BencodeError::new(format!(
+                        "Invalid byte for bencode value: '{}'",
+                        c as char
+                    ))
+                    .at(pos))
+                }
+                None if open > 0 => {
+                    return Err(BencodeError::with_kind(
+                        BencodeErrorKind::Truncated,
+                        "Unterminated list or dict value",
+                    )
+                    .at(pos))
+                }
+                None => {
+                    return Err(BencodeError::with_kind(
+                        BencodeErrorKind::Truncated,
+                        "Empty bytes while trying to parse bencode value",
+                    )
+                    .at(pos))
+                }
+            }
+            if open == 0 {
+                return Ok(pos);
+            }
+        }
+    }
+
+    /// Return the range of the contents of the string starting at `pos`
+    fn scan_str(raw_content: &[u8], pos: usize) -> Result<Range<usize>, BencodeError> {
+        let Some(colon) = raw_content[pos..].iter().position(|&byte| byte == b':') else {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::Truncated,
+                String::from("Invalid string value"),
+            )
+            .at(raw_content.len()));
+        };
+        let len_bytes = &raw_content[pos..pos + colon];
+        let Some(str_len) = std::str::from_utf8(len_bytes)
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+        else {
+            return Err(
+                BencodeError::new(format!("Invalid string length '{:?}'", len_bytes)).at(pos),
+            );
+        };
+        let start = pos + colon + 1;
+        match start.checked_add(str_len) {
+            Some(end) if end <= raw_content.len() => Ok(start..end),
+            _ => Err(BencodeError::with_kind(
+                BencodeErrorKind::Truncated,
+                "Unexpected end of string value",
+            )
+            .at(raw_content.len())),
+        }
+    }
+
+    /// Parse the given file, which must not be larger than [`DEFAULT_MAX_FILE_SIZE`]
     pub fn from_file(path: &str) -> Result<Bencode, BencodeError> {
-        let Ok(bytes) = fs::read(path) else {
-            return Err(BencodeError::new("invalid file contents"))
+        Self::decode(&Self::read_file(path)?)
+    }
+
+    /// Same as [`BencodeParser::from_file`] with a custom maximum file size in bytes
+    pub fn from_file_with_max_size(path: &str, max_size: u64) -> Result<Bencode, BencodeError> {
+        Self::decode(&Self::read_file_with_max_size(path, max_size)?)
+    }
+
+    pub fn from_file_strict(path: &str) -> Result<Bencode, BencodeError> {
+        Self::decode_strict(&Self::read_file(path)?)
+    }
+
+    pub(crate) fn read_file(path: &str) -> Result<Vec<u8>, BencodeError> {
+        Self::read_file_with_max_size(path, DEFAULT_MAX_FILE_SIZE)
+    }
+
+    /// Read the whole file, failing without reading it
+    /// if it is larger than `max_size` bytes.
+    pub(crate) fn read_file_with_max_size(
+        path: &str,
+        max_size: u64,
+    ) -> Result<Vec<u8>, BencodeError> {
+        let io_err = |err| BencodeError::with_source("invalid file contents", err);
+        let file = File::open(path).map_err(io_err)?;
+        let too_large = |size| {
+            BencodeError::with_kind(
+                BencodeErrorKind::LimitExceeded,
+                format!(
+                    "file size of {} bytes exceeds the maximum of {} bytes",
+                    size, max_size
+                ),
+            )
         };
 
-        Self::decode(&bytes)
+        let size = file.metadata().map_err(io_err)?.len();
+        if size > max_size {
+            return Err(too_large(size));
+        }
+
+        // The size may be unknown or change while reading (pipes, growing files...)
+        let mut bytes = Vec::with_capacity(size as usize);
+        file.take(max_size + 1)
+            .read_to_end(&mut bytes)
+            .map_err(io_err)?;
+        if bytes.len() as u64 > max_size {
+            return Err(too_large(bytes.len() as u64));
+        }
+        Ok(bytes)
     }
 
     pub fn encode(value: &Bencode) -> Vec<u8> {
@@ -60,7 +781,7 @@ impl BencodeParser {
         }
     }
 
-    fn encode_number(value: &u64) -> Vec<u8> {
+    fn encode_number(value: &i64) -> Vec<u8> {
         format!("i{}e", value).as_bytes().to_vec()
     }
 
@@ -84,10 +805,16 @@ impl BencodeParser {
         vec
     }
 
+    /// Keys are written sorted by their raw bytes, as required by the spec,
+    /// whatever their insertion order. Otherwise re-encoding a decoded
+    /// `info` dict could produce different bytes, hence a different info_hash.
     fn encode_dict(value: &IndexMap<ByteString, Bencode>) -> Vec<u8> {
+        let mut entries: Vec<_> = value.iter().collect();
+        entries.sort_by(|(k1, _), (k2, _)| k1.0.cmp(&k2.0));
+
         let mut vec = Vec::new();
         vec.extend("d".as_bytes());
-        for (key, value) in value.into_iter() {
+        for (key, value) in entries {
             let encoded_value = Self::encode(value);
             let encoded_key = Self::encode_text(key);
             vec.extend(encoded_key);
@@ -98,118 +825,171 @@ impl BencodeParser {
         vec
     }
 
-    fn parse<'a>(iterator: &mut impl Iterator<Item = &'a u8>) -> Result<Bencode, BencodeError> {
-        if let Some(&byte) = iterator.next() {
-            return match char::from_u32(byte as u32) {
-                Some('i') => Self::parse_int(iterator),
-                Some('l') => Self::parse_list(iterator),
-                Some('d') => Self::parse_dict(iterator),
-                Some(c) if Self::is_digit(c) => Self::parse_str(c, iterator),
-                Some(c) => Err(BencodeError::new(format!(
-                    "Invalid byte for bencode value: '{}'",
-                    c
-                ))),
-                None => Err(BencodeError::new(
-                    "Empty bytes while trying to parse bencode value",
-                )),
-            };
-        }
-
-        Err(BencodeError::new(String::from("Invalid Bencode content")))
+    /// Parse the next value. `depth` is the number of lists
+    /// and dicts the value is nested in.
+    ///
+    /// Lists and dicts being parsed are kept on an explicit stack rather than
+    /// parsed recursively, so deeply nested content can't overflow the stack.
+    fn parse(
+        iterator: &mut impl ByteSource,
+        options: &DecodeOptions,
+        depth: usize,
+    ) -> Result<Bencode, BencodeError> {
+        Self::parse_with_stack(iterator, options, depth, &mut Vec::new())
     }
 
-    fn parse_dict<'a>(
-        iterator: &mut impl Iterator<Item = &'a u8>,
+    /// Same as [`BencodeParser::parse`], with the lists and dicts being
+    /// parsed (innermost last) left on `stack` when an error occurs.
+    fn parse_with_stack(
+        iterator: &mut impl ByteSource,
+        options: &DecodeOptions,
+        depth: usize,
+        stack: &mut Vec<Container>,
     ) -> Result<Bencode, BencodeError> {
-        let mut map = IndexMap::new();
+        loop {
+            let Some(byte) = iterator.next_byte()? else {
+                return Err(match stack.last() {
+                    Some(container) => Self::unterminated(container.name()),
+                    None => BencodeError::with_kind(
+                        BencodeErrorKind::Truncated,
+                        "Empty bytes while trying to parse bencode value",
+                    ),
+                });
+            };
+            let c = byte as char;
 
-        while let Some(&byte) = iterator.next() {
-            match char::from_u32(byte as u32) {
-                Some(c) if Self::is_digit(c) => {
-                    // we first handle the dictionary key
-                    if let Bencode::Text(text) = Self::parse_str(c, iterator)? {
-                        // Value can be anything, including dictionaries
-                        let value = Self::parse(iterator)?;
-                        map.insert(text, value);
-                    } else {
-                        return Err(BencodeError::new(format!("Invalid string byte {}", c)));
+            let value = match stack.last_mut() {
+                // Closing the innermost list or dict
+                Some(Container::List(_)) | Some(Container::Dict(_, None)) if c == 'e' => {
+                    stack.pop().expect("the stack isn't empty").into_value()
+                }
+                // Dictionary key, its value comes next
+                Some(Container::Dict(map, key @ None)) => {
+                    if !Self::is_digit(c) {
+                        return Err(BencodeError::new(format!(
+                            "Invalid string byte for dict length '{}'",
+                            c
+                        )));
                     }
+                    Self::check_collection_len(options, map.len())?;
+                    *key = Some(Self::parse_str(c, iterator)?);
+                    continue;
                 }
-                // Closing the dictionary
-                Some('e') => break,
-                Some(c) => {
-                    return Err(BencodeError::new(format!(
-                        "Invalid string byte for dict length '{}'",
-                        c
-                    )))
+                parent => {
+                    if let Some(Container::List(items)) = parent {
+                        Self::check_collection_len(options, items.len())?;
+                    }
+                    match c {
+                        'i' => Self::parse_int(iterator)?,
+                        'l' | 'd' => {
+                            Self::nest(options, depth + stack.len())?;
+                            stack.push(match c {
+                                'l' => Container::List(Vec::new()),
+                                _ => Container::Dict(IndexMap::new(), None),
+                            });
+                            continue;
+                        }
+                        c if Self::is_digit(c) => Bencode::Text(Self::parse_str(c, iterator)?),
+                        // a terminator without any list or dict to close
+                        'e' if depth == 0 && stack.is_empty() => {
+                            return Err(BencodeError::new(
+                                "unexpected end-of-container marker at top level",
+                            ))
+                        }
+                        c => {
+                            return Err(BencodeError::new(format!(
+                                "Invalid byte for bencode value: '{}'",
+                                c
+                            )))
+                        }
+                    }
+                }
+            };
+
+            // Hand the complete value over to its container, if any
+            match stack.last_mut() {
+                None => return Ok(value),
+                Some(Container::List(items)) => items.push(value),
+                Some(Container::Dict(map, key)) => {
+                    let key = key.take().expect("dict values always follow a key");
+                    map.insert(key, value);
                 }
-                None => return Err(BencodeError::new("Empty byte for dict key")),
             }
         }
-
-        Ok(Bencode::Dict(map))
     }
 
-    fn parse_list<'a>(
-        iterator: &mut impl Iterator<Item = &'a u8>,
-    ) -> Result<Bencode, BencodeError> {
-        let mut acc = Vec::new();
-        while let Some(&byte) = iterator.next() {
-            match char::from_u32(byte as u32) {
-                // nested list
-                Some('l') => {
-                    let list = Self::parse_list(iterator)?;
-                    acc.push(list);
-                }
-                // dictionary
-                Some('d') => {
-                    let dict = Self::parse_dict(iterator)?;
-                    acc.push(dict);
-                }
-                // integers
-                Some('i') => {
-                    let number = Self::parse_int(iterator)?;
-                    acc.push(number);
-                }
-                // strings
-                Some(c) if Self::is_digit(c) => {
-                    let str = Self::parse_str(c, iterator)?;
-                    acc.push(str);
-                }
-                // end of list, closing it
-                Some('e') => break,
-                Some(c) => return Err(BencodeError::new(format!("Invalid char {}", c))),
-                None => break,
-            }
+    /// Depth of a list or dict found at the given depth
+    fn nest(options: &DecodeOptions, depth: usize) -> Result<usize, BencodeError> {
+        if depth >= options.max_depth {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::LimitExceeded,
+                format!("nesting exceeds the maximum depth of {}", options.max_depth),
+            ));
         }
+        Ok(depth + 1)
+    }
 
-        Ok(Bencode::List(acc))
+    /// Point the error at the byte being parsed when it occurred, given the
+    /// number of bytes left unread: the last byte read, or the end of the
+    /// content when it ended too soon.
+    fn locate(err: BencodeError, raw_content: &[u8], remaining: usize) -> BencodeError {
+        let position = match err.kind() {
+            BencodeErrorKind::Truncated => raw_content.len(),
+            _ => (raw_content.len() - remaining).saturating_sub(1),
+        };
+        err.at(position)
+    }
+
+    /// The input ended before the closing `e` of an integer, list or dict
+    fn unterminated(container: &str) -> BencodeError {
+        BencodeError::with_kind(
+            BencodeErrorKind::Truncated,
+            format!("unterminated {}, missing the closing 'e'", container),
+        )
+    }
+
+    fn check_collection_len(options: &DecodeOptions, len: usize) -> Result<(), BencodeError> {
+        if len >= options.max_collection_len {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::LimitExceeded,
+                format!(
+                    "list or dict exceeds the maximum of {} entries",
+                    options.max_collection_len
+                ),
+            ));
+        }
+        Ok(())
     }
 
     /// Whether the given character is a valid number character
     fn is_digit(c: char) -> bool {
-        ('0'..='9').contains(&c)
+        c.is_ascii_digit()
     }
 
-    fn parse_str<'a>(
+    fn parse_str(
         length_start: char,
-        mut iterator: &mut impl Iterator<Item = &'a u8>,
-    ) -> Result<Bencode, BencodeError> {
+        iterator: &mut impl ByteSource,
+    ) -> Result<ByteString, BencodeError> {
         let mut str_len = Vec::new();
         str_len.push(length_start);
 
         // First we need to read the string length until we reach the `:`.
-        for &byte in &mut iterator {
+        while let Some(byte) = iterator.next_byte()? {
             match char::from_u32(byte as u32) {
                 Some(c) if Self::is_digit(c) => str_len.push(c),
-                Some(c) if c == ':' => break,
+                Some(':') => break,
                 Some(c) => {
                     return Err(BencodeError::new(format!(
                         "invalid string length character: '{}'",
                         c
                     )))
                 }
-                None => return Err(BencodeError::new(String::from("Invalid string value"))),
+                None => {
+                    return Err(BencodeError::with_kind(
+                        BencodeErrorKind::Truncated,
+                        String::from("Invalid string value"),
+                    ))
+                }
             }
         }
 
@@ -223,33 +1003,48 @@ impl BencodeParser {
             )))
         };
 
-        let mut str_value = Vec::with_capacity(str_len as usize);
-
-        for byte in iterator.take(str_len as usize) {
-            str_value.push(*byte);
-        }
-
-        Ok(Bencode::Text(ByteString::from_vec(str_value)))
+        Ok(ByteString::from_vec(iterator.read_string(str_len)?))
     }
 
-    fn parse_int<'a>(iterator: &mut impl Iterator<Item = &'a u8>) -> Result<Bencode, BencodeError> {
+    fn parse_int(iterator: &mut impl ByteSource) -> Result<Bencode, BencodeError> {
         let mut acc = Vec::new();
-        for &byte in iterator {
-            match char::from_u32(byte as u32) {
-                Some(c) if Self::is_digit(c) => acc.push(c),
-                Some('e') => break,
-                Some(c) => {
+        loop {
+            let Some(byte) = iterator.next_byte()? else {
+                return Err(Self::unterminated("integer"));
+            };
+            match char::from(byte) {
+                c if Self::is_digit(c) => acc.push(c),
+                // the sign may only come first
+                '-' if acc.is_empty() => acc.push('-'),
+                'e' => break,
+                c => {
                     return Err(BencodeError::new(format!(
                         "invalid char '{}' when parsing integers",
                         c
                     )))
                 }
-                None => break,
             }
         }
         let text_num: String = acc.iter().collect();
+        let digits = text_num.strip_prefix('-').unwrap_or(&text_num);
+        if digits.is_empty() {
+            return Err(BencodeError::new(format!(
+                "integer '{}' has no digits",
+                text_num
+            )));
+        }
+        // The spec only allows a single representation of every number
+        if digits.len() > 1 && digits.starts_with('0') {
+            return Err(BencodeError::new(format!(
+                "integer '{}' has leading zeros",
+                text_num
+            )));
+        }
+        if text_num == "-0" {
+            return Err(BencodeError::new("integer '-0' is not allowed"));
+        }
         text_num
-            .parse::<u64>()
+            .parse::<i64>()
             .map(Bencode::Number)
             .or(Err(BencodeError::new(format!(
                 "invalid integer value '{}'",
@@ -261,6 +1056,8 @@ impl BencodeParser {
 #[cfg(test)]
 mod tests {
 
+    use std::fs;
+
     use super::*;
 
     #[test]
@@ -268,7 +1065,55 @@ mod tests {
         let str = "64520998877";
         let content = format!("i{}e", str).as_bytes().to_vec();
         let result = BencodeParser::decode(&content).unwrap();
-        assert_eq!(result, Bencode::Number(str.parse::<u64>().unwrap()));
+        assert_eq!(result, Bencode::Number(str.parse::<i64>().unwrap()));
+    }
+
+    #[test]
+    fn should_round_trip_negative_integers() {
+        let content = b"i-42e";
+        let result = BencodeParser::decode(content).unwrap();
+        assert_eq!(result, Bencode::Number(-42));
+        assert_eq!(result.as_u64(), None);
+        assert_eq!(BencodeParser::encode(&result), content);
+    }
+
+    #[test]
+    fn should_only_accept_the_sign_in_front_of_integers() {
+        assert!(BencodeParser::decode(b"i4-2e").is_err());
+        assert!(BencodeParser::decode(b"i--42e").is_err());
+    }
+
+    #[test]
+    fn should_reject_non_canonical_integers() {
+        for (raw, message) in [
+            (&b"i03e"[..], "integer '03' has leading zeros at byte 3"),
+            (b"i00e", "integer '00' has leading zeros at byte 3"),
+            (b"i-03e", "integer '-03' has leading zeros at byte 4"),
+            (b"i-0e", "integer '-0' is not allowed at byte 3"),
+            (b"ie", "integer '' has no digits at byte 1"),
+            (b"i-e", "integer '-' has no digits at byte 2"),
+        ] {
+            let err = BencodeParser::decode(raw).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        assert_eq!(BencodeParser::decode(b"i0e").unwrap(), Bencode::Number(0));
+        assert_eq!(BencodeParser::decode(b"i10e").unwrap(), Bencode::Number(10));
+    }
+
+    #[test]
+    fn should_encode_dict_keys_in_byte_order() {
+        let value = Bencode::Dict(IndexMap::from([
+            (ByteString::new("zebra"), Bencode::Number(1)),
+            (ByteString::from_vec(vec![0xff]), Bencode::Number(2)),
+            (ByteString::new("Zebra"), Bencode::Number(3)),
+            (ByteString::new("apple"), Bencode::Number(4)),
+        ]));
+
+        assert_eq!(
+            BencodeParser::encode(&value),
+            b"d5:Zebrai3e5:applei4e5:zebrai1e1:\xffi2ee"
+        );
     }
 
     #[test]
@@ -471,4 +1316,428 @@ mod tests {
         let new_decoded_value = BencodeParser::decode(&encoded_value).unwrap();
         assert_eq!(decoded_value, new_decoded_value);
     }
+
+    #[test]
+    fn should_reject_trailing_bytes_in_strict_mode() {
+        let content = "4:spamgarbage".as_bytes().to_vec();
+
+        let lenient = BencodeParser::decode(&content).unwrap();
+        assert_eq!(lenient, Bencode::Text(ByteString::new("spam")));
+
+        let strict = BencodeParser::decode_strict(&content);
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn should_record_the_span_of_dict_values() {
+        let content = "d3:cow3:moo4:spaml1:a1:bee".as_bytes().to_vec();
+        let (value, spans) = BencodeParser::decode_dict_with_spans(&content).unwrap();
+
+        assert_eq!(value, BencodeParser::decode(&content).unwrap());
+        let spam_span = spans.get(&ByteString::new("spam")).unwrap().clone();
+        assert_eq!(&content[spam_span], "l1:a1:be".as_bytes());
+    }
+
+    #[test]
+    fn should_expose_the_underlying_error_as_source() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "no such torrent");
+        let error = BencodeError::with_source("invalid file contents", io_error);
+
+        let source = error.source().unwrap();
+        let io_error = source.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound);
+        assert!(BencodeError::new("no source").source().is_none());
+
+        let Err(error) = BencodeParser::from_file("tests/does_not_exist.torrent") else {
+            panic!("expected the missing file to fail");
+        };
+        assert!(error.source().unwrap().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn should_canonicalize_nested_dictionaries() {
+        let mut value = Bencode::Dict(IndexMap::from([
+            (ByteString::new("zebra"), Bencode::Number(1)),
+            (
+                ByteString::new("nested"),
+                Bencode::Dict(IndexMap::from([
+                    (ByteString::new("b"), Bencode::Number(2)),
+                    (
+                        ByteString::new("a"),
+                        Bencode::List(vec![Bencode::Dict(IndexMap::from([
+                            (ByteString::new("y"), Bencode::Number(3)),
+                            (ByteString::new("x"), Bencode::Number(4)),
+                        ]))]),
+                    ),
+                ])),
+            ),
+            (
+                ByteString::new("apple"),
+                Bencode::Text(ByteString::new("red")),
+            ),
+        ]));
+
+        value.canonicalize();
+
+        let encoded = BencodeParser::encode(&value);
+        assert_eq!(
+            String::from_utf8(encoded).unwrap(),
+            "d5:apple3:red6:nestedd1:ald1:xi4e1:yi3eee1:bi2ee5:zebrai1ee"
+        );
+    }
+
+    #[test]
+    fn should_find_the_span_of_a_single_dict_value() {
+        let raw = b"d1:ali1ed1:xi2eee4:infod6:lengthi3ee1:z1:ye";
+        let span = BencodeParser::find_dict_value_span(raw, b"info")
+            .unwrap()
+            .unwrap();
+        assert_eq!(&raw[span], b"d6:lengthi3ee");
+        assert_eq!(
+            BencodeParser::find_dict_value_span(raw, b"missing").unwrap(),
+            None
+        );
+        assert!(BencodeParser::find_dict_value_span(b"d4:info5:abce", b"info").is_err());
+    }
+
+    #[test]
+    fn should_reject_strings_longer_than_the_remaining_input() {
+        let err = BencodeParser::decode(b"l10:abcde").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "string length 10 exceeds remaining 5 bytes at byte 9"
+        );
+
+        let err = BencodeParser::decode(b"100:abc").unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::Truncated);
+        assert_eq!(
+            err.to_string(),
+            "string length 100 exceeds remaining 3 bytes at byte 7"
+        );
+    }
+
+    /// Hands out a single byte per `read` call
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&byte, rest)), Some(first)) => {
+                    *first = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn should_decode_from_a_reader() {
+        let raw = fs::read("tests/ubuntu_sample.torrent").unwrap();
+        let expected = BencodeParser::decode(&raw).unwrap();
+
+        let mut cursor = io::Cursor::new(&raw);
+        assert_eq!(BencodeParser::decode_from(&mut cursor).unwrap(), expected);
+        assert_eq!(cursor.position(), raw.len() as u64);
+        assert_eq!(
+            BencodeParser::decode_from(ByteByByte(&raw)).unwrap(),
+            expected
+        );
+
+        // nothing past the value is consumed
+        let mut cursor = io::Cursor::new(b"i42e4:spam".to_vec());
+        assert_eq!(
+            BencodeParser::decode_from(&mut cursor).unwrap(),
+            Bencode::Number(42)
+        );
+        assert_eq!(
+            BencodeParser::decode_from(&mut cursor).unwrap(),
+            Bencode::Text(ByteString::new("spam"))
+        );
+    }
+
+    #[test]
+    fn should_report_streams_ending_mid_value() {
+        let err = BencodeParser::decode_from(ByteByByte(b"d4:spaml1:a")).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::Truncated);
+        assert_eq!(
+            err.to_string(),
+            "unterminated list, missing the closing 'e' at byte 11"
+        );
+
+        let err = BencodeParser::decode_from(ByteByByte(b"l10:abc")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stream ended after 3 of the 10 bytes of a string at byte 7"
+        );
+
+        let err = BencodeParser::decode_from(ByteByByte(b"lxe")).unwrap_err();
+        assert_eq!(err.position(), Some(1));
+    }
+
+    #[test]
+    fn should_enforce_the_limits_on_streams() {
+        // hostile peers: endless nesting and a string longer than the stream allows
+        let nested = vec![b'l'; 10_000];
+        let err = BencodeParser::decode_from(ByteByByte(&nested)).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::LimitExceeded);
+        assert_eq!(err.position(), Some(100));
+
+        let err = BencodeParser::decode_from(ByteByByte(b"99999999999:abc")).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::LimitExceeded);
+        assert_eq!(
+            err.to_string(),
+            "stream exceeds the maximum of 16777216 bytes at byte 12"
+        );
+
+        let options = DecodeOptions {
+            max_total_bytes: 8,
+            ..DecodeOptions::unlimited()
+        };
+        let mut cursor = io::Cursor::new(b"4:spam4:eggs".to_vec());
+        assert!(BencodeParser::decode_from_with_options(&mut cursor, &options).is_ok());
+        let err = BencodeParser::decode_from_with_options(ByteByByte(b"l4:spam4:eggse"), &options)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stream exceeds the maximum of 8 bytes at byte 8"
+        );
+    }
+
+    #[test]
+    fn should_return_the_partial_tree_of_truncated_content() {
+        let raw = b"d8:announce8:http://t4:infod6:lengthi10e5:filesld6:lengthi3e4:pathl1:a";
+        let (partial, err) = BencodeParser::decode_lenient(raw);
+
+        let err = err.unwrap();
+        assert_eq!(err.kind(), BencodeErrorKind::Truncated);
+        assert_eq!(err.position(), Some(raw.len()));
+        let expected = BencodeParser::decode(
+            b"d8:announce8:http://t4:infod6:lengthi10e5:filesld6:lengthi3e4:pathl1:aeeeee",
+        )
+        .unwrap();
+        assert_eq!(partial, Some(expected));
+
+        // the string being parsed is dropped, along with its key
+        let (partial, err) = BencodeParser::decode_lenient(b"d1:ai1e1:b5:ab");
+        assert!(err.is_some());
+        assert_eq!(partial, Some(BencodeParser::decode(b"d1:ai1ee").unwrap()));
+
+        let (value, err) = BencodeParser::decode_lenient(b"li1ee");
+        assert_eq!(value, Some(BencodeParser::decode(b"li1ee").unwrap()));
+        assert!(err.is_none());
+        assert_eq!(BencodeParser::decode_lenient(b"x").0, None);
+    }
+
+    #[test]
+    fn should_reject_blank_content_up_front() {
+        let err = BencodeParser::decode(b"").unwrap_err();
+        assert_eq!(err.to_string(), "empty content is not bencode");
+        assert_eq!(err.kind(), BencodeErrorKind::Truncated);
+
+        let err = BencodeParser::decode(b"\0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "content of 1 bytes is not bencode: only whitespace and control bytes"
+        );
+        assert_eq!(err.position(), None);
+
+        let err = BencodeParser::decode_strict(b" \r\n\t ").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "content of 5 bytes is not bencode: only whitespace and control bytes"
+        );
+        assert_eq!(err.kind(), BencodeErrorKind::Invalid);
+    }
+
+    #[test]
+    fn should_parse_deeply_nested_values_without_recursing() {
+        const DEPTH: usize = 50_000;
+        let mut raw = vec![b'l'; DEPTH];
+        raw.extend(b"i1e");
+        raw.extend(vec![b'e'; DEPTH]);
+
+        let mut value =
+            BencodeParser::decode_with_options(&raw, &DecodeOptions::unlimited()).unwrap();
+        let mut depth = 0;
+        while let Bencode::List(items) = &mut value {
+            value = items.pop().unwrap();
+            depth += 1;
+        }
+        assert_eq!(depth, DEPTH);
+        assert_eq!(value, Bencode::Number(1));
+    }
+
+    #[test]
+    fn should_enforce_the_max_depth_option() {
+        let options = DecodeOptions {
+            max_depth: 2,
+            ..DecodeOptions::unlimited()
+        };
+        assert!(BencodeParser::decode_with_options(b"ld1:ai1eee", &options).is_ok());
+        let err = BencodeParser::decode_with_options(b"ld1:alee", &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "nesting exceeds the maximum depth of 2 at byte 5"
+        );
+
+        // hostile input: the default limit kicks in long before the end
+        let raw = vec![b'l'; 10_000];
+        let err = BencodeParser::decode_with_options(&raw, &DecodeOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::LimitExceeded);
+        assert_eq!(err.position(), Some(100));
+    }
+
+    #[test]
+    fn should_enforce_the_max_total_bytes_option() {
+        let options = DecodeOptions {
+            max_total_bytes: 8,
+            ..DecodeOptions::unlimited()
+        };
+        assert!(BencodeParser::decode_with_options(b"4:spam", &options).is_ok());
+        let err = BencodeParser::decode_with_options(b"9:spam eggs", &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "content of 11 bytes exceeds the maximum of 8 bytes"
+        );
+    }
+
+    #[test]
+    fn should_enforce_the_max_collection_len_option() {
+        let options = DecodeOptions {
+            max_collection_len: 2,
+            ..DecodeOptions::unlimited()
+        };
+        assert!(BencodeParser::decode_with_options(b"li1ei2ee", &options).is_ok());
+        assert!(BencodeParser::decode_with_options(b"d1:ai1e1:bi2ee", &options).is_ok());
+
+        let err = BencodeParser::decode_with_options(b"li1ei2ei3ee", &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "list or dict exceeds the maximum of 2 entries at byte 7"
+        );
+        assert!(BencodeParser::decode_with_options(b"d1:ai1e1:bi2e1:ci3ee", &options).is_err());
+    }
+
+    #[test]
+    fn should_reject_stray_and_unterminated_containers() {
+        let error = |raw: &[u8]| BencodeParser::decode(raw).unwrap_err().to_string();
+
+        assert_eq!(
+            error(b"e"),
+            "unexpected end-of-container marker at top level at byte 0"
+        );
+        assert_eq!(
+            error(b"l"),
+            "unterminated list, missing the closing 'e' at byte 1"
+        );
+        assert_eq!(
+            error(b"li1e"),
+            "unterminated list, missing the closing 'e' at byte 4"
+        );
+        assert_eq!(
+            error(b"d"),
+            "unterminated dict, missing the closing 'e' at byte 1"
+        );
+        assert_eq!(
+            error(b"d1:ai1e"),
+            "unterminated dict, missing the closing 'e' at byte 7"
+        );
+    }
+
+    #[test]
+    fn should_detect_containers_missing_their_terminator() {
+        for raw in [
+            &b"l4:spam"[..],
+            b"d3:foo3:bar",
+            b"ll4:spame",
+            b"d3:fooli1ee",
+            b"i42",
+            b"i-",
+            b"li42",
+        ] {
+            let err = BencodeParser::decode(raw).unwrap_err();
+            assert_eq!(err.kind(), BencodeErrorKind::Truncated, "{:?}", raw);
+            assert!(err.to_string().starts_with("unterminated"), "{}", err);
+        }
+
+        assert!(BencodeParser::decode_dict_with_spans(b"d3:foo3:bar").is_err());
+        assert!(BencodeParser::find_dict_value_span(b"d3:foo3:bar", b"info").is_err());
+        let err = BencodeParser::decode_from(&mut &b"i42"[..]).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::Truncated);
+    }
+
+    #[test]
+    fn should_bound_the_debug_output() {
+        let small = BencodeParser::decode(b"d4:listl4:spami-42ee4:name3:abce").unwrap();
+        assert_eq!(
+            format!("{:?}", small),
+            "Dict({list: List([Text(spam), Number(-42)]), name: Text(abc)})"
+        );
+
+        // thousands of files and a long comment
+        let mut raw = b"d7:comment1000:".to_vec();
+        raw.extend([b'x'; 1000]);
+        raw.extend(b"4:infod5:filesl");
+        for _ in 0..5000 {
+            raw.extend(b"d6:lengthi1e4:pathl8:file.txtee");
+        }
+        raw.extend(b"eee");
+        let large = BencodeParser::decode(&raw).unwrap();
+        let debug = format!("{:?}", large);
+        assert!(debug.len() < 2048, "{}", debug);
+        assert!(debug.contains("Text(<1000 bytes>)"));
+        assert!(debug.contains("...4984 more"));
+
+        let mut deep = b"l".repeat(100);
+        deep.extend(b"e".repeat(100));
+        let deep = BencodeParser::decode(&deep).unwrap();
+        assert!(format!("{:?}", deep).ends_with("List(<1 items>)])])])])])])])])])])])])])])])])"));
+    }
+
+    #[test]
+    fn should_report_the_position_of_invalid_bytes() {
+        let raw = b"d8:announce3:url4:infod6:lengthi12x4e4:name1:aee";
+        let err = BencodeParser::decode(raw).unwrap_err();
+        assert_eq!(err.position(), Some(34));
+        assert_eq!(raw[34], b'x');
+        assert_eq!(
+            err.to_string(),
+            "invalid char 'x' when parsing integers at byte 34"
+        );
+
+        let err = BencodeParser::decode_dict_with_spans(b"d3:fooi1e3:barX").unwrap_err();
+        assert_eq!(err.position(), Some(14));
+        let err = BencodeParser::find_dict_value_span(b"d3:fooi1e3:barX", b"baz").unwrap_err();
+        assert_eq!(err.position(), Some(14));
+        let err = BencodeParser::decode_strict(b"i1e2:ab").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+    }
+
+    #[test]
+    fn should_access_the_value_of_the_expected_variant() {
+        let value = BencodeParser::decode(b"d4:listli1ei2ee6:numberi-3e4:text3:abce").unwrap();
+
+        let list = value.get("list").unwrap();
+        assert_eq!(
+            list.as_list(),
+            Some(&[Bencode::Number(1), Bencode::Number(2)][..])
+        );
+        assert_eq!(list.as_dict(), None);
+        assert_eq!(list.get("list"), None);
+
+        let number = value.get("number").unwrap();
+        assert_eq!(number.as_number(), Some(-3));
+        assert_eq!(number.as_u64(), None);
+        assert_eq!(number.as_text(), None);
+
+        let text = value.get("text").unwrap();
+        assert_eq!(text.as_text(), Some(&ByteString::new("abc")));
+        assert_eq!(text.as_number(), None);
+        assert_eq!(text.as_list(), None);
+
+        assert_eq!(value.as_dict().map(IndexMap::len), Some(3));
+        assert_eq!(value.get("missing"), None);
+    }
 }
@@ -4,13 +4,17 @@ use std::{
     ops::Deref,
 };
 
+/// Binary strings longer than this are only partially shown
+pub const MAX_HEX_DUMP_LEN: usize = 64;
+
 #[derive(Clone, Eq)]
 pub struct ByteString(pub Vec<u8>);
 
 /// a ByteString is just a string of bytes. It does not have encoding information.
 ///
 /// Note: We can try to decode it as UTF-8 when calling `to_string` but we fallback
-/// to just show the byte array length instead if that fails.
+/// to a hex dump of the bytes instead if that fails, truncated past
+/// [`MAX_HEX_DUMP_LEN`] bytes.
 impl ByteString {
     pub fn new(str: &str) -> Self {
         Self(str.as_bytes().to_vec())
@@ -20,7 +24,15 @@ impl ByteString {
         Self(vec)
     }
 
-    fn compare_vectors(a: &Vec<u8>, b: &Vec<u8>) -> bool {
+    /// Split the bytes around the first occurrence of `delim`,
+    /// without assuming they are valid UTF-8.
+    /// The delimiter itself isn't part of either side.
+    pub fn split_once_byte(&self, delim: u8) -> Option<(&[u8], &[u8])> {
+        let index = self.iter().position(|&byte| byte == delim)?;
+        Some((&self[..index], &self[index + 1..]))
+    }
+
+    fn compare_vectors(a: &[u8], b: &[u8]) -> bool {
         let matching = a.iter().zip(b.iter()).filter(|&(a, b)| a == b).count();
         matching == a.len() && matching == b.len()
     }
@@ -30,8 +42,14 @@ impl ByteString {
             // For strings that are UTF-8 encoded, we can safely format them
             write!(f, "{}", text)
         } else {
-            // For raw strings, we can just display the raw array size for now
-            write!(f, "{:?}", self.len())
+            // For raw strings (hashes, peer ids...), the bytes are shown in hex
+            for byte in self.iter().take(MAX_HEX_DUMP_LEN) {
+                write!(f, "{:02x}", byte)?;
+            }
+            if self.len() > MAX_HEX_DUMP_LEN {
+                write!(f, "... ({} bytes)", self.len())?;
+            }
+            Ok(())
         }
     }
 }
@@ -62,8 +80,113 @@ impl PartialEq for ByteString {
     }
 }
 
+/// Compare the bytes with the UTF-8 bytes of the text,
+/// e.g. `key == "interval"` without building a ByteString first
+impl PartialEq<str> for ByteString {
+    fn eq(&self, other: &str) -> bool {
+        Self::compare_vectors(self, other.as_bytes())
+    }
+}
+
+impl PartialEq<&str> for ByteString {
+    fn eq(&self, other: &&str) -> bool {
+        Self::compare_vectors(self, other.as_bytes())
+    }
+}
+
+impl PartialEq<[u8]> for ByteString {
+    fn eq(&self, other: &[u8]) -> bool {
+        Self::compare_vectors(self, other)
+    }
+}
+
+impl PartialEq<ByteString> for str {
+    fn eq(&self, other: &ByteString) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<ByteString> for &str {
+    fn eq(&self, other: &ByteString) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<ByteString> for [u8] {
+    fn eq(&self, other: &ByteString) -> bool {
+        other == self
+    }
+}
+
 impl Hash for ByteString {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.0.hash(state)
     }
 }
+
+/// Wipe the bytes, e.g. a private tracker passkey, once they are no longer needed.
+/// Wrap secrets in a [`SecretByteString`] to have them wiped when dropped.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for ByteString {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// [`ByteString`] whose bytes are wiped when it is dropped. Plain byte strings
+/// aren't, so their bytes can still be moved out of them.
+#[cfg(feature = "zeroize")]
+pub type SecretByteString = zeroize::Zeroizing<ByteString>;
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_split_binary_content_on_a_byte() {
+        let value = ByteString::from_vec(vec![0xff, 0xfe, b':', 0x00, b':', 0xc3]);
+        assert!(core::str::from_utf8(&value).is_err());
+
+        let (before, after) = value.split_once_byte(b':').unwrap();
+        assert_eq!(before, &[0xff, 0xfe]);
+        assert_eq!(after, &[0x00, b':', 0xc3]);
+
+        assert_eq!(value.split_once_byte(b'/'), None);
+    }
+
+    #[test]
+    fn should_compare_with_text_and_bytes() {
+        let key = ByteString::new("interval");
+        assert!(key == "interval");
+        assert!(key == *"interval");
+        assert!(key == b"interval"[..]);
+        assert!("interval" == key);
+        assert!(*"interval" == key);
+        assert!(b"interval"[..] == key);
+
+        assert!(key != "Interval");
+        assert!(key != b"interval\0"[..]);
+        assert!(b"interva"[..] != key);
+
+        let binary = ByteString::from_vec(vec![0xff, 0xfe]);
+        assert!(binary != "\u{fffd}\u{fffd}");
+        assert!(binary == [0xff, 0xfe][..]);
+    }
+
+    #[test]
+    fn should_show_binary_content_in_hex() {
+        assert_eq!(ByteString::new("announce").to_string(), "announce");
+        assert_eq!(format!("{:?}", ByteString::new("ünïcode")), "ünïcode");
+
+        let hash = ByteString::from_vec(vec![0x99, 0xc8, 0x2b, 0x00, 0xff]);
+        assert_eq!(hash.to_string(), "99c82b00ff");
+        assert_eq!(format!("{:?}", hash), "99c82b00ff");
+
+        let pieces = ByteString::from_vec([0xfe, 0x01].repeat(100));
+        assert_eq!(
+            pieces.to_string(),
+            format!("{}... (200 bytes)", "fe01".repeat(32))
+        );
+    }
+}
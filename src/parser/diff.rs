@@ -0,0 +1,130 @@
+use super::bencode::Bencode;
+
+/// A single difference between two bencode values.
+/// `path` locates the value from the root, e.g. `info.files[1].length`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BencodeDiff {
+    /// Dict key or list item only present in the second value
+    Added { path: String, value: Bencode },
+    /// Dict key or list item only present in the first value
+    Removed { path: String, value: Bencode },
+    /// Value present in both, but different
+    Changed {
+        path: String,
+        from: Bencode,
+        to: Bencode,
+    },
+}
+
+/// Compute the differences going from `a` to `b`, recursing into
+/// dicts and lists so only the innermost changed values are reported.
+pub fn diff(a: &Bencode, b: &Bencode) -> Vec<BencodeDiff> {
+    let mut diffs = Vec::new();
+    diff_values(a, b, "", &mut diffs);
+    diffs
+}
+
+fn diff_values(a: &Bencode, b: &Bencode, path: &str, diffs: &mut Vec<BencodeDiff>) {
+    match (a, b) {
+        (Bencode::Dict(a_map), Bencode::Dict(b_map)) => {
+            for (key, a_value) in a_map.iter() {
+                let key_path = join_key(path, &key.to_string());
+                match b_map.get(key) {
+                    Some(b_value) => diff_values(a_value, b_value, &key_path, diffs),
+                    None => diffs.push(BencodeDiff::Removed {
+                        path: key_path,
+                        value: a_value.clone(),
+                    }),
+                }
+            }
+            for (key, b_value) in b_map.iter() {
+                if !a_map.contains_key(key) {
+                    diffs.push(BencodeDiff::Added {
+                        path: join_key(path, &key.to_string()),
+                        value: b_value.clone(),
+                    });
+                }
+            }
+        }
+        (Bencode::List(a_list), Bencode::List(b_list)) => {
+            for index in 0..a_list.len().max(b_list.len()) {
+                let item_path = format!("{}[{}]", path, index);
+                match (a_list.get(index), b_list.get(index)) {
+                    (Some(a_item), Some(b_item)) => diff_values(a_item, b_item, &item_path, diffs),
+                    (Some(a_item), None) => diffs.push(BencodeDiff::Removed {
+                        path: item_path,
+                        value: a_item.clone(),
+                    }),
+                    (None, Some(b_item)) => diffs.push(BencodeDiff::Added {
+                        path: item_path,
+                        value: b_item.clone(),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (a, b) if a != b => diffs.push(BencodeDiff::Changed {
+            path: path.to_string(),
+            from: a.clone(),
+            to: b.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn join_key(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::bencode::BencodeParser;
+    use crate::parser::byte_string::ByteString;
+
+    use super::*;
+
+    #[test]
+    fn should_report_a_single_nested_change() {
+        let a = BencodeParser::decode(b"d8:announce3:foo4:infod6:lengthi10e4:name1:xee").unwrap();
+        let b = BencodeParser::decode(b"d8:announce3:foo4:infod6:lengthi20e4:name1:xee").unwrap();
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![BencodeDiff::Changed {
+                path: String::from("info.length"),
+                from: Bencode::Number(10),
+                to: Bencode::Number(20),
+            }]
+        );
+    }
+
+    #[test]
+    fn should_report_added_and_removed_keys_and_items() {
+        let a = BencodeParser::decode(b"d7:comment2:hi4:listli1ei2eee").unwrap();
+        let b = BencodeParser::decode(b"d4:listli1ee4:name1:xe").unwrap();
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                BencodeDiff::Removed {
+                    path: String::from("comment"),
+                    value: Bencode::Text(ByteString::new("hi")),
+                },
+                BencodeDiff::Removed {
+                    path: String::from("list[1]"),
+                    value: Bencode::Number(2),
+                },
+                BencodeDiff::Added {
+                    path: String::from("name"),
+                    value: Bencode::Text(ByteString::new("x")),
+                },
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt::Write;
+
+use crate::hash::hex;
+
+use super::bencode::Bencode;
+use super::byte_string::ByteString;
+
+/// Piece of JSON left to write
+enum Frame<'a> {
+    Value(&'a Bencode),
+    Key(&'a ByteString),
+    Punctuation(char),
+}
+
+impl Bencode {
+    /// Convert the value to (compact) JSON, to inspect it with JSON tools.
+    ///
+    /// Numbers become JSON numbers, lists arrays and dicts objects.
+    /// Text that is valid UTF-8 becomes a JSON string, binary text (hashes,
+    /// compact peers...) an object such as `{"bytes_hex": "0a1b"}`.
+    /// Binary dict keys are written as hex strings, as JSON keys must be strings.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        // written iteratively, as deeply nested values are valid bencode
+        let mut stack = vec![Frame::Value(self)];
+        while let Some(frame) = stack.pop() {
+            match frame {
+                Frame::Punctuation(c) => json.push(c),
+                Frame::Key(key) => {
+                    match std::str::from_utf8(key) {
+                        Ok(key) => write_string(&mut json, key),
+                        Err(_) => write_string(&mut json, &hex(key)),
+                    }
+                    json.push(':');
+                }
+                Frame::Value(Bencode::Number(number)) => {
+                    let _ = write!(json, "{}", number);
+                }
+                Frame::Value(Bencode::Text(text)) => match std::str::from_utf8(text) {
+                    Ok(text) => write_string(&mut json, text),
+                    Err(_) => {
+                        json.push_str("{\"bytes_hex\":");
+                        write_string(&mut json, &hex(text));
+                        json.push('}');
+                    }
+                },
+                Frame::Value(Bencode::List(items)) => {
+                    json.push('[');
+                    stack.push(Frame::Punctuation(']'));
+                    for (index, item) in items.iter().enumerate().rev() {
+                        stack.push(Frame::Value(item));
+                        if index > 0 {
+                            stack.push(Frame::Punctuation(','));
+                        }
+                    }
+                }
+                Frame::Value(Bencode::Dict(map)) => {
+                    json.push('{');
+                    stack.push(Frame::Punctuation('}'));
+                    for (index, (key, value)) in map.iter().enumerate().rev() {
+                        stack.push(Frame::Value(value));
+                        stack.push(Frame::Key(key));
+                        if index > 0 {
+                            stack.push(Frame::Punctuation(','));
+                        }
+                    }
+                }
+            }
+        }
+        json
+    }
+}
+
+fn write_string(json: &mut String, text: &str) {
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::parser::bencode::{BencodeParser, DecodeOptions};
+
+    use super::*;
+
+    #[test]
+    fn should_convert_mixed_values_to_json() {
+        let value = Bencode::Dict(IndexMap::from([
+            (ByteString::new("interval"), Bencode::Number(-1800)),
+            (
+                ByteString::new("name"),
+                Bencode::Text(ByteString::new("say \"hi\"\n")),
+            ),
+            (
+                ByteString::new("peer id"),
+                Bencode::Text(ByteString::from_vec(vec![0xff, 0x00, 0x1b])),
+            ),
+            (
+                ByteString::new("files"),
+                Bencode::List(vec![
+                    Bencode::List(vec![Bencode::Number(1), Bencode::Number(2)]),
+                    Bencode::List(Vec::new()),
+                    Bencode::Dict(IndexMap::new()),
+                ]),
+            ),
+            (
+                ByteString::from_vec(vec![0xc3, 0x28]),
+                Bencode::Text(ByteString::new("")),
+            ),
+        ]));
+
+        assert_eq!(
+            value.to_json(),
+            r#"{"interval":-1800,"name":"say \"hi\"\n","peer id":{"bytes_hex":"ff001b"},"files":[[1,2],[],{}],"c328":""}"#
+        );
+    }
+
+    #[test]
+    fn should_convert_deeply_nested_values() {
+        let depth = DecodeOptions::default().max_depth;
+        let mut raw = vec![b'l'; depth];
+        raw.extend(vec![b'e'; depth]);
+        let json = BencodeParser::decode(&raw).unwrap().to_json();
+        assert_eq!(json.len(), 2 * depth);
+        assert!(json.starts_with("[[") && json.ends_with("]]"));
+    }
+}
@@ -0,0 +1,173 @@
+use super::bencode::BencodeError;
+
+/// A magnet link (BEP 9), such as
+/// `magnet:?xt=urn:btih:<info hash>&dn=<name>&tr=<tracker URL>`.
+/// The torrent's info dict itself has to be fetched from peers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Magnet {
+    pub info_hash: [u8; 20],
+    /// display name (`dn`), the suggested name of the torrent
+    pub name: Option<String>,
+    /// tracker URLs (`tr`), in the order they appear in the link
+    pub trackers: Vec<String>,
+}
+
+impl Magnet {
+    pub fn parse(uri: &str) -> Result<Self, BencodeError> {
+        let Some(query) = uri.strip_prefix("magnet:?") else {
+            return Err(BencodeError::new(format!("'{}' is not a magnet link", uri)));
+        };
+
+        let mut info_hash = None;
+        let mut name = None;
+        let mut trackers = Vec::new();
+        for param in query.split('&') {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            match key {
+                "xt" if info_hash.is_none() => {
+                    // other kinds of exact topics (e.g. `urn:btmh:` for v2) are skipped
+                    if let Some(hash) = value.strip_prefix("urn:btih:") {
+                        info_hash = Some(parse_btih(hash)?);
+                    }
+                }
+                "dn" => name = Some(percent_decode(&value.replace('+', " "))?),
+                "tr" => trackers.push(percent_decode(value)?),
+                _ => {}
+            }
+        }
+
+        let Some(info_hash) = info_hash else {
+            return Err(BencodeError::new(format!(
+                "magnet link '{}' has no 'xt=urn:btih:' info hash",
+                uri
+            )));
+        };
+        Ok(Self {
+            info_hash,
+            name,
+            trackers,
+        })
+    }
+}
+
+/// BitTorrent info hashes come either hex encoded (40 chars)
+/// or base32 encoded (32 chars)
+fn parse_btih(hash: &str) -> Result<[u8; 20], BencodeError> {
+    let decoded = match hash.len() {
+        40 => decode_hex(hash),
+        32 => decode_base32(hash),
+        _ => None,
+    };
+    decoded.ok_or_else(|| BencodeError::new(format!("invalid btih info hash '{}'", hash)))
+}
+
+fn decode_hex(hash: &str) -> Option<[u8; 20]> {
+    if !hash.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0; 20];
+    for (byte, pair) in bytes.iter_mut().zip(hash.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// RFC 4648 base32, without padding as 32 chars make exactly 20 bytes
+fn decode_base32(hash: &str) -> Option<[u8; 20]> {
+    let mut bytes = [0; 20];
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    let mut index = 0;
+    for c in hash.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes[index] = (buffer >> bits) as u8;
+            index += 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn percent_decode(value: &str) -> Result<String, BencodeError> {
+    let err = || BencodeError::new(format!("invalid percent-encoding in '{}'", value));
+
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = [iter.next().ok_or_else(err)?, iter.next().ok_or_else(err)?];
+        if !hex.iter().all(u8::is_ascii_hexdigit) {
+            return Err(err());
+        }
+        let hex = std::str::from_utf8(&hex).map_err(|_| err())?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|_| err())?);
+    }
+    String::from_utf8(bytes).map_err(|_| err())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UBUNTU_INFO_HASH: [u8; 20] = [
+        0x99, 0xc8, 0x2b, 0xb7, 0x35, 0x05, 0xa3, 0xc0, 0xb4, 0x53, 0xf9, 0xfa, 0x0e, 0x88, 0x1d,
+        0x6e, 0x5a, 0x32, 0xa0, 0xc1,
+    ];
+
+    #[test]
+    fn should_parse_hex_info_hashes() {
+        let magnet = Magnet::parse(
+            "magnet:?xt=urn:btih:99c82bb73505a3c0b453f9fa0e881d6e5a32a0c1&dn=ubuntu-22.10-desktop-amd64.iso",
+        )
+        .unwrap();
+
+        assert_eq!(magnet.info_hash, UBUNTU_INFO_HASH);
+        assert_eq!(
+            magnet.name.as_deref(),
+            Some("ubuntu-22.10-desktop-amd64.iso")
+        );
+        assert!(magnet.trackers.is_empty());
+    }
+
+    #[test]
+    fn should_parse_base32_info_hashes() {
+        let magnet = Magnet::parse("magnet:?xt=urn:btih:thecxnzvawr4bnct7h5a5ca5nzndfigb").unwrap();
+        assert_eq!(magnet.info_hash, UBUNTU_INFO_HASH);
+        assert_eq!(magnet.name, None);
+    }
+
+    #[test]
+    fn should_decode_every_tracker() {
+        let magnet = Magnet::parse(
+            "magnet:?xt=urn:btih:99C82BB73505A3C0B453F9FA0E881D6E5A32A0C1&dn=Ubuntu+22.10\
+             &tr=https%3A%2F%2Ftorrent.ubuntu.com%2Fannounce&tr=udp%3A%2F%2Ftracker.example%3A1337",
+        )
+        .unwrap();
+
+        assert_eq!(magnet.name.as_deref(), Some("Ubuntu 22.10"));
+        assert_eq!(
+            magnet.trackers,
+            vec![
+                "https://torrent.ubuntu.com/announce",
+                "udp://tracker.example:1337"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_reject_links_without_a_btih_info_hash() {
+        assert!(Magnet::parse("magnet:?dn=name&tr=http%3A%2F%2Ft").is_err());
+        assert!(Magnet::parse("magnet:?xt=urn:btih:abc").is_err());
+        assert!(Magnet::parse("http://example.com").is_err());
+    }
+}
@@ -1,10 +1,31 @@
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
 use indexmap::IndexMap;
 
-use super::bencode::{Bencode, BencodeError, BencodeParser};
+use super::bencode::{
+    Bencode, BencodeError, BencodeErrorKind, BencodeParser, DEFAULT_MAX_FILE_SIZE,
+};
 use super::byte_string::ByteString;
+use crate::hash::{self, Sha1Backend};
 
 type Dict = IndexMap<ByteString, Bencode>;
 
+/// Top-level keys of a torrent parsed into a field of [`MetaInfo`]
+const PARSED_KEYS: [&str; 8] = [
+    "announce",
+    "announce-list",
+    "comment",
+    "created by",
+    "creation date",
+    "encoding",
+    "info",
+    "piece layers",
+];
+
 /// Meta-info files (.torrent) according to the (unofficial) spec.
 /// See the [Metainfo spec here](https://wiki.theory.org/BitTorrentSpecification#Metainfo_File_Structure) (unofficial)
 #[derive(Debug)]
@@ -13,7 +34,8 @@ pub struct MetaInfo {
     /// The announce URL of the tracker
     pub announce: String,
     // extension to the official specification, offering backwards-compatibility.
-    pub announce_list: Option<Vec<String>>,
+    /// Tiers of tracker URLs (BEP 12), tried in order for failover
+    pub announce_list: Option<Vec<Vec<String>>>,
     pub creation_date: Option<u64>,
     /// free-form textual comments of the author
     pub comment: Option<String>,
@@ -21,61 +43,274 @@ pub struct MetaInfo {
     /// the string encoding format used to generate the pieces part
     /// of the info dictionary in the .torrent metafile
     pub encoding: Option<String>,
+    /// BEP 52 (v2): merkle layer hashes, keyed by the `pieces root` of each file
+    /// larger than a piece. Values are the concatenated 32-byte SHA256 hashes
+    /// of the layer whose hashes cover one piece each.
+    pub piece_layers: Option<IndexMap<ByteString, ByteString>>,
+    /// top-level keys no field is parsed into (`url-list`, `nodes`...),
+    /// written back as they are by [`MetaInfo::to_bencode`]
+    other_keys: Dict,
+    /// raw contents the torrent was parsed from, see [`MetaInfo::write_unchanged`].
+    /// `None` when built from an already decoded [`Bencode`] value.
+    source: Option<Vec<u8>>,
 }
 
 impl MetaInfo {
-    /// Parse the given file (.torrent) in a valid MetaInfo data structure
+    /// Parse the given file (.torrent) in a valid MetaInfo data structure.
+    ///
+    /// Some clients append their own resume data after the torrent dict,
+    /// so anything following the top-level dict is ignored.
     pub fn from_file(path: &str) -> Result<Self, BencodeError> {
-        let bencode = BencodeParser::from_file(path)?;
+        let bytes = BencodeParser::read_file(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Same as [`MetaInfo::from_file`] with a custom maximum file size in bytes,
+    /// instead of [`DEFAULT_MAX_FILE_SIZE`](super::bencode::DEFAULT_MAX_FILE_SIZE).
+    pub fn from_file_with_max_size(path: &str, max_size: u64) -> Result<Self, BencodeError> {
+        let bytes = BencodeParser::read_file_with_max_size(path, max_size)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Same as [`MetaInfo::from_file`], but rejects files with trailing
+    /// bytes after the top-level dict. Useful for validation tooling.
+    pub fn from_file_strict(path: &str) -> Result<Self, BencodeError> {
+        let bytes = BencodeParser::read_file(path)?;
+        BencodeParser::decode_strict(&bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Read a torrent from any reader (file, socket, in-memory buffer...),
+    /// which must not yield more than [`DEFAULT_MAX_FILE_SIZE`] bytes
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, BencodeError> {
+        let mut bytes = Vec::new();
+        reader
+            .take(DEFAULT_MAX_FILE_SIZE + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|err| BencodeError::with_source("could not read torrent contents", err))?;
+        if bytes.len() as u64 > DEFAULT_MAX_FILE_SIZE {
+            return Err(BencodeError::with_kind(
+                BencodeErrorKind::LimitExceeded,
+                format!(
+                    "torrent exceeds the maximum of {} bytes",
+                    DEFAULT_MAX_FILE_SIZE
+                ),
+            ));
+        }
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parse the raw contents of a torrent file.
+    ///
+    /// The original bytes of the `info` dict are kept as they are,
+    /// so the info_hash matches the one computed by other clients
+    /// even if the dict isn't canonically encoded.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BencodeError> {
+        let (bencode, spans) = BencodeParser::decode_dict_with_spans(bytes)?;
+        let info_bytes = spans
+            .get(&ByteString::new("info"))
+            .map(|info_span| &bytes[info_span.clone()]);
+        let mut meta_info = Self::from_bencode(&bencode, info_bytes)?;
+        meta_info.source = Some(bytes.to_vec());
+        Ok(meta_info)
+    }
+
+    /// Write the torrent back exactly as it was read, byte for byte, rather than
+    /// re-encoding it: key order and any non-canonical encoding are preserved,
+    /// so the info_hash can't change. Changes made since (e.g. with
+    /// [`MetaInfo::add_tracker`]) are not written.
+    ///
+    /// Fails without writing anything if the torrent wasn't read from raw
+    /// bytes (e.g. built with `MetaInfo::try_from(&Bencode)`), as there are
+    /// no original bytes to write then. Use [`MetaInfo::to_file`] instead.
+    pub fn write_unchanged(&self, path: &str) -> io::Result<()> {
+        let Some(source) = &self.source else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the torrent wasn't read from raw bytes, there are none to write unchanged",
+            ));
+        };
+        fs::write(path, source)
+    }
+
+    /// Rebuild the torrent dict out of the parsed fields, including changes
+    /// made since (e.g. with [`MetaInfo::add_tracker`]) and the top-level keys
+    /// no field is parsed into. The `info` dict is decoded from its original
+    /// bytes, but encoding the result sorts its keys, which changes the
+    /// info_hash of torrents that aren't canonically encoded: use
+    /// [`MetaInfo::to_bytes`] to write the torrent.
+    pub fn to_bencode(&self) -> Bencode {
+        let mut dict = self.top_level_fields();
+        let info = BencodeParser::decode(&self.info.bencode_value)
+            .unwrap_or_else(|_| self.info.to_bencode());
+        dict.insert(ByteString::new("info"), info);
+        dict.sort_by(|k1, _, k2, _| k1.0.cmp(&k2.0));
+        Bencode::Dict(dict)
+    }
+
+    /// Encode the torrent like [`MetaInfo::to_bencode`], except that the
+    /// original bytes of the `info` dict are copied as they are,
+    /// so the info_hash doesn't change.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut dict = self.top_level_fields();
+        // only there to sort the keys, its original bytes are written instead
+        dict.insert(ByteString::new("info"), Bencode::Number(0));
+        dict.sort_by(|k1, _, k2, _| k1.0.cmp(&k2.0));
+
+        let mut bytes = vec![b'd'];
+        for (key, value) in &dict {
+            bytes.extend(BencodeParser::encode(&Bencode::Text(key.clone())));
+            if key == "info" {
+                bytes.extend(&self.info.bencode_value);
+            } else {
+                bytes.extend(BencodeParser::encode(value));
+            }
+        }
+        bytes.push(b'e');
+        bytes
+    }
+
+    /// Encode the torrent with [`MetaInfo::to_bytes`] and write it to `path`
+    pub fn to_file(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// Top-level torrent dict, except the `info` dict
+    fn top_level_fields(&self) -> Dict {
+        let text = |text: &str| Bencode::Text(ByteString::new(text));
+        let mut dict = self.other_keys.clone();
+        dict.insert(ByteString::new("announce"), text(&self.announce));
+        if let Some(tiers) = &self.announce_list {
+            let tiers = tiers
+                .iter()
+                .map(|urls| Bencode::List(urls.iter().map(|url| text(url)).collect()))
+                .collect();
+            dict.insert(ByteString::new("announce-list"), Bencode::List(tiers));
+        }
+        if let Some(comment) = &self.comment {
+            dict.insert(ByteString::new("comment"), text(comment));
+        }
+        if let Some(created_by) = &self.created_by {
+            dict.insert(ByteString::new("created by"), text(created_by));
+        }
+        if let Some(creation_date) = self.creation_date {
+            dict.insert(
+                ByteString::new("creation date"),
+                Bencode::Number(creation_date as i64),
+            );
+        }
+        if let Some(encoding) = &self.encoding {
+            dict.insert(ByteString::new("encoding"), text(encoding));
+        }
+        if let Some(piece_layers) = &self.piece_layers {
+            let layers = piece_layers
+                .iter()
+                .map(|(root, layer)| (root.clone(), Bencode::Text(layer.clone())))
+                .collect();
+            dict.insert(ByteString::new("piece layers"), Bencode::Dict(layers));
+        }
+        dict
+    }
+
+    /// Compute the info_hash of the given raw torrent contents without
+    /// decoding it: the `info` value is located and its bytes hashed as they are.
+    /// Handy to index many torrents when nothing else is needed.
+    pub fn info_hash_only(bytes: &[u8]) -> Result<[u8; 20], BencodeError> {
+        let Some(info_span) = BencodeParser::find_dict_value_span(bytes, b"info")? else {
+            return Err(BencodeError::new("missing 'info' dict in torrent"));
+        };
+        Ok(hash::sha1(&bytes[info_span]))
+    }
+
+    /// Add a tracker to the given tier of the announce list, creating a new
+    /// last tier if `tier` is past the existing ones. The info dict is left
+    /// untouched, so the info_hash doesn't change.
+    ///
+    /// Per BEP 12 the `announce` URL is ignored when an announce list is present,
+    /// so it becomes the first tier if the torrent had no announce list yet.
+    /// A tracker already in any tier isn't added again.
+    pub fn add_tracker(&mut self, tier: usize, url: &str) {
+        let announce = &self.announce;
+        let tiers = self
+            .announce_list
+            .get_or_insert_with(|| match announce.as_str() {
+                "" => Vec::new(),
+                _ => vec![vec![announce.clone()]],
+            });
+
+        if tiers.iter().flatten().any(|existing| existing == url) {
+            return;
+        }
+        match tiers.get_mut(tier) {
+            Some(urls) => urls.push(url.to_string()),
+            None => tiers.push(vec![url.to_string()]),
+        }
+    }
+
+    /// Remove a tracker from every tier of the announce list, dropping tiers
+    /// left empty. If it is the `announce` URL, that one is replaced by the
+    /// first remaining tracker, or left empty. Returns whether the tracker was found.
+    pub fn remove_tracker(&mut self, url: &str) -> bool {
+        let mut removed = false;
+        if let Some(tiers) = self.announce_list.as_mut() {
+            for urls in tiers.iter_mut() {
+                let len = urls.len();
+                urls.retain(|existing| existing != url);
+                removed |= urls.len() != len;
+            }
+            tiers.retain(|urls| !urls.is_empty());
+            if tiers.is_empty() {
+                self.announce_list = None;
+            }
+        }
+
+        if self.announce == url {
+            self.announce = self
+                .announce_list
+                .iter()
+                .flatten()
+                .flatten()
+                .next()
+                .cloned()
+                .unwrap_or_default();
+            removed = true;
+        }
+        removed
+    }
+
+    /// `info_bytes` are the original bytes of the info dict, if known
+    fn from_bencode(bencode: &Bencode, info_bytes: Option<&[u8]>) -> Result<Self, BencodeError> {
         match bencode {
             Bencode::Dict(dict) => {
-                let info = Info::from(&dict)?;
-
-                if let Bencode::Text(announce) = get_value("announce", &dict)? {
-                    let announce_list =
-                        dict.get(&ByteString::new("announce-list"))
-                            .and_then(|l| match l {
-                                Bencode::List(list) => {
-                                    let res = list
-                                        .iter()
-                                        .filter_map(|v| match v {
-                                            // Announce list is always a list of lists of strings (Vec<Vec<String>>)
-                                            // so we need to flatten them out
-                                            Bencode::List(list) => {
-                                                let mut values = Vec::with_capacity(list.len());
-                                                for text in list.iter() {
-                                                    if let Bencode::Text(announce_url) = text {
-                                                        values.push(announce_url.to_string());
-                                                    }
-                                                }
-                                                Some(values)
-                                            }
-                                            _ => None,
-                                        })
-                                        .flatten()
-                                        .collect::<Vec<String>>();
-                                    Some(res)
-                                }
-                                _ => None,
-                            });
-                    let comment = get_optional_str("comment", &dict);
-                    let created_by = get_optional_str("created by", &dict);
-                    let encoding = get_optional_str("encoding", &dict);
-                    let creation_date =
-                        dict.get(&ByteString::new("creation date"))
-                            .and_then(|date| match date {
-                                Bencode::Number(date_int) => Some(*date_int),
-                                _ => None,
-                            });
+                let info = Info::from(dict, info_bytes)?;
+
+                if let Bencode::Text(announce) = get_value("announce", dict)? {
+                    let announce_list = parse_announce_list(dict);
+                    let comment = get_optional_str("comment", dict);
+                    let created_by = get_optional_str("created by", dict);
+                    let encoding = get_optional_str("encoding", dict);
+                    let creation_date = dict
+                        .get(&ByteString::new("creation date"))
+                        .and_then(Bencode::as_u64);
+
+                    let piece_layers = Self::parse_piece_layers(dict)?;
+                    let other_keys = dict
+                        .iter()
+                        .filter(|(key, _)| !PARSED_KEYS.iter().any(|parsed| *key == parsed))
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect();
 
                     return Ok(Self {
                         info,
-                        announce: announce.to_string(),
+                        announce: trim_url(announce),
                         announce_list,
                         comment,
                         created_by,
                         encoding,
                         creation_date,
+                        piece_layers,
+                        other_keys,
+                        source: None,
                     });
                 }
 
@@ -84,6 +319,78 @@ impl MetaInfo {
             _ => Err(parsing_error("Invalid metainfo torrent file")),
         }
     }
+
+    fn parse_piece_layers(
+        dict: &Dict,
+    ) -> Result<Option<IndexMap<ByteString, ByteString>>, BencodeError> {
+        let Some(value) = dict.get(&ByteString::new("piece layers")) else {
+            return Ok(None);
+        };
+        let Bencode::Dict(layers) = value else {
+            return Err(parsing_error("'piece layers' must be a dictionary"));
+        };
+
+        let mut piece_layers = IndexMap::with_capacity(layers.len());
+        for (pieces_root, layer) in layers {
+            let Bencode::Text(layer) = layer else {
+                return Err(parsing_error("'piece layers' values must be byte strings"));
+            };
+            piece_layers.insert(pieces_root.clone(), layer.clone());
+        }
+        Ok(Some(piece_layers))
+    }
+}
+
+/// Build a [`MetaInfo`] from an already decoded torrent, e.g. one received
+/// via metadata exchange. As the original bytes aren't available, the info
+/// dict is re-encoded to fill in [`Info::bencode_value`].
+impl TryFrom<&Bencode> for MetaInfo {
+    type Error = BencodeError;
+
+    fn try_from(value: &Bencode) -> Result<Self, Self::Error> {
+        Self::from_bencode(value, None)
+    }
+}
+
+/// Length in bytes of a single SHA1 piece hash
+pub const PIECE_HASH_LEN: usize = 20;
+
+/// Smallest piece length considered sane, the size of a single block (16 KiB)
+pub const MIN_PIECE_LENGTH: u64 = 16 * 1024;
+
+/// Non-fatal issues reported by [`Info::lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InfoWarning {
+    /// `piece length` is below [`MIN_PIECE_LENGTH`]
+    PieceLengthTooSmall(u64),
+    /// By convention, `piece length` is a power of two
+    PieceLengthNotPowerOfTwo(u64),
+}
+
+impl Display for InfoWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InfoWarning::PieceLengthTooSmall(length) => write!(
+                f,
+                "piece length {} is below the minimum of {}",
+                length, MIN_PIECE_LENGTH
+            ),
+            InfoWarning::PieceLengthNotPowerOfTwo(length) => {
+                write!(f, "piece length {} is not a power of two", length)
+            }
+        }
+    }
+}
+
+/// Version of the torrent format an info dict follows, see [`Info::format_version`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TorrentVersion {
+    /// SHA1 `pieces` only (BEP 3)
+    V1,
+    /// `meta version` 2 and a `file tree` only (BEP 52)
+    V2,
+    /// both v1 and v2 metadata, for clients of either version
+    Hybrid,
 }
 
 #[derive(Debug)]
@@ -100,31 +407,315 @@ pub struct Info {
     /// Here, "private" may be read as "no external peer source".
     pub private: bool,
     pub file_info: FileMode,
+    /// bencoded bytes of the info dict, as found in the source when
+    /// available. This is what the info_hash is computed from.
     pub bencode_value: Vec<u8>,
+    /// info_hash computed on first use, see [`Info::info_hash`]
+    info_hash: OnceLock<[u8; 20]>,
 }
 
 impl Info {
-    fn from(dict: &Dict) -> Result<Self, BencodeError> {
-        if let Bencode::Dict(info_dict) = get_value("info", dict)? {
-            if let Bencode::Number(piece_length) = get_value("piece length", info_dict)? {
-                if let Bencode::Text(pieces) = get_value("pieces", info_dict)? {
-                    let private = info_dict
-                        .get(&ByteString::new("private"))
-                        .map(|v| &Bencode::Number(1) == v)
-                        .unwrap_or_else(|| false);
-                    let file_info = Self::parse_file_info(info_dict)?;
-                    let bencode_value = Bencode::Dict(info_dict.clone());
-                    return Ok(Self {
-                        piece_length: *piece_length,
-                        pieces: pieces.clone(),
-                        private,
-                        file_info,
-                        bencode_value: BencodeParser::encode(&bencode_value),
-                    });
+    /// Build an info dict out of its parts rather than parsing it, e.g. for
+    /// tests or once the metadata has been received from peers.
+    /// The name is the one of `file_info`.
+    ///
+    /// Fails if `pieces` isn't made of whole 20-byte hashes or if the pieces
+    /// don't cover the total length of the files (see [`Info::validate_length`]).
+    pub fn new(
+        piece_length: u64,
+        pieces: ByteString,
+        file_info: FileMode,
+        private: bool,
+    ) -> Result<Self, BencodeError> {
+        check_pieces_len(&pieces)?;
+
+        let mut info = Self {
+            piece_length,
+            pieces,
+            private,
+            file_info,
+            bencode_value: Vec::new(),
+            info_hash: OnceLock::new(),
+        };
+        info.validate_length()?;
+        info.bencode_value = BencodeParser::encode(&info.to_bencode());
+        Ok(info)
+    }
+
+    /// The info dict these values were read from, or would be written as
+    fn to_bencode(&self) -> Bencode {
+        let text = |text: &str| Bencode::Text(ByteString::new(text));
+        let number = |number: u64| Bencode::Number(number as i64);
+        let mut dict = Dict::new();
+        dict.insert(ByteString::new("name"), text(self.name()));
+        dict.insert(ByteString::new("piece length"), number(self.piece_length));
+        dict.insert(
+            ByteString::new("pieces"),
+            Bencode::Text(self.pieces.clone()),
+        );
+        if self.private {
+            dict.insert(ByteString::new("private"), Bencode::Number(1));
+        }
+        match &self.file_info {
+            FileMode::Single(file) => {
+                dict.insert(ByteString::new("length"), number(file.length));
+                if let Some(md5sum) = &file.md5sum {
+                    dict.insert(ByteString::new("md5sum"), text(md5sum));
                 }
             }
+            FileMode::Multi(multi) => {
+                let files = multi
+                    .files
+                    .iter()
+                    .map(|file| {
+                        let mut item = Dict::new();
+                        item.insert(ByteString::new("length"), number(file.length));
+                        if let Some(md5sum) = &file.md5sum {
+                            item.insert(ByteString::new("md5sum"), text(md5sum));
+                        }
+                        let path = file.path.iter().map(|part| text(part)).collect();
+                        item.insert(ByteString::new("path"), Bencode::List(path));
+                        Bencode::Dict(item)
+                    })
+                    .collect();
+                dict.insert(ByteString::new("files"), Bencode::List(files));
+            }
+        }
+        Bencode::Dict(dict)
+    }
+
+    /// SHA1 of the bencoded info dict, identifying the torrent.
+    /// Computed over [`Info::bencode_value`], i.e. the original bytes of the
+    /// dict when parsed from a file, so it matches what other clients compute.
+    ///
+    /// The hash is computed on the first call and cached, so it is stale
+    /// if `bencode_value` is changed afterwards.
+    pub fn info_hash(&self) -> [u8; 20] {
+        self.info_hash_with(&hash::DefaultSha1::default())
+    }
+
+    fn info_hash_with(&self, hasher: &impl Sha1Backend) -> [u8; 20] {
+        *self
+            .info_hash
+            .get_or_init(|| hasher.digest(&self.bencode_value))
+    }
+
+    /// Number of pieces described by the `pieces` hashes
+    pub fn num_pieces(&self) -> usize {
+        self.pieces.len() / PIECE_HASH_LEN
+    }
+
+    /// Total length in bytes of the torrent content, all files included.
+    /// Torrents whose files add up to more than `u64::MAX` bytes are rejected
+    /// when parsed, the total saturates if `file_info` was changed since.
+    pub fn total_length(&self) -> u64 {
+        self.checked_total_length().unwrap_or(u64::MAX)
+    }
+
+    /// Total length, `None` if the files add up to more than `u64::MAX` bytes
+    fn checked_total_length(&self) -> Option<u64> {
+        match &self.file_info {
+            FileMode::Single(file) => Some(file.length),
+            FileMode::Multi(multi) => multi
+                .files
+                .iter()
+                .try_fold(0u64, |total, file| total.checked_add(file.length)),
+        }
+    }
+
+    /// Indices of the pieces holding some of the file at `file_index`, including
+    /// the pieces it shares with the previous and next files. `None` if there
+    /// is no such file or it is empty, as no piece holds any of it then,
+    /// and if the files before it add up to more than `u64::MAX` bytes.
+    pub fn pieces_for_file(&self, file_index: usize) -> Option<RangeInclusive<usize>> {
+        let lengths: Vec<u64> = match &self.file_info {
+            FileMode::Single(file) => vec![file.length],
+            FileMode::Multi(multi) => multi.files.iter().map(|file| file.length).collect(),
+        };
+        let length = *lengths.get(file_index)?;
+        if length == 0 || self.piece_length == 0 {
+            return None;
+        }
+        let offset = lengths[..file_index]
+            .iter()
+            .try_fold(0u64, |offset, length| offset.checked_add(*length))?;
+        let first = offset / self.piece_length;
+        let last = offset.checked_add(length - 1)? / self.piece_length;
+        Some(first as usize..=last as usize)
+    }
+
+    /// Length of the last piece, which holds whatever is left after the full
+    /// pieces and is usually shorter than `piece length`.
+    pub fn last_piece_length(&self) -> u64 {
+        let total_length = self.total_length();
+        if total_length == 0 || self.piece_length == 0 {
+            return 0;
+        }
+        match total_length % self.piece_length {
+            0 => self.piece_length,
+            remainder => remainder,
+        }
+    }
+
+    /// Check that the pieces cover exactly the total length of the content:
+    /// `(num_pieces - 1) * piece_length + last_piece_length == total_length`
+    ///
+    /// Parsed torrents are checked already, except v2-only ones without pieces.
+    pub fn validate_length(&self) -> Result<(), BencodeError> {
+        if self.piece_length == 0 {
+            return Err(parsing_error("piece length must not be 0"));
+        }
+        let Some(total_length) = self.checked_total_length() else {
+            return Err(files_too_long());
+        };
+        let covered = match self.num_pieces() as u64 {
+            0 => Some(0),
+            num_pieces => (num_pieces - 1)
+                .checked_mul(self.piece_length)
+                .and_then(|length| length.checked_add(self.last_piece_length())),
+        };
+        let Some(covered) = covered else {
+            return Err(BencodeError::new(format!(
+                "{} pieces of {} bytes cover more than {} bytes",
+                self.num_pieces(),
+                self.piece_length,
+                u64::MAX
+            )));
+        };
+        if covered != total_length {
+            return Err(BencodeError::new(format!(
+                "{} pieces of {} bytes cover {} bytes instead of the total length of {} bytes",
+                self.num_pieces(),
+                self.piece_length,
+                covered,
+                total_length
+            )));
         }
-        Err(parsing_error("Invalid meta_info"))
+        Ok(())
+    }
+
+    /// The advisory name of the torrent: the filename in single-file mode
+    /// or the directory name in multi-file mode.
+    pub fn name(&self) -> &str {
+        match &self.file_info {
+            FileMode::Single(file) => &file.name,
+            FileMode::Multi(files) => &files.name,
+        }
+    }
+
+    /// The torrent name sanitized to a single, flat path component that
+    /// is safe to create on Linux, macOS and Windows.
+    ///
+    /// Path separators, null/control bytes and characters reserved on
+    /// Windows are replaced by `_`, trailing dots and spaces are dropped
+    /// and reserved Windows device names (`CON`, `NUL`, `COM1`...) are prefixed.
+    pub fn safe_name(&self) -> String {
+        sanitize_file_name(self.name())
+    }
+
+    /// Iterate over the 20-byte SHA1 hash of each piece, borrowed
+    /// straight from `pieces` without allocating.
+    pub fn piece_hash_iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.pieces.chunks_exact(PIECE_HASH_LEN)
+    }
+
+    /// Check a downloaded piece against its SHA1 hash. `false` if `index`
+    /// is out of range or `pieces` isn't made of whole 20-byte hashes.
+    pub fn verify_piece(&self, index: usize, data: &[u8]) -> bool {
+        if !self.pieces.len().is_multiple_of(PIECE_HASH_LEN) {
+            return false;
+        }
+        self.piece_hash_iter()
+            .nth(index)
+            .is_some_and(|expected_hash| hash::sha1(data) == expected_hash)
+    }
+
+    /// Whether the torrent is a v1, v2 or hybrid one, which decides the
+    /// info_hash and piece verification to use. It is v2 when the info dict
+    /// has `meta version` 2 and a `file tree`, and v1 when it has `pieces`.
+    pub fn format_version(&self) -> TorrentVersion {
+        let value = |key: &[u8]| {
+            BencodeParser::find_dict_value_span(&self.bencode_value, key)
+                .ok()
+                .flatten()
+                .map(|span| &self.bencode_value[span])
+        };
+        let v2 = value(b"meta version") == Some(&b"i2e"[..]) && value(b"file tree").is_some();
+        match (v2, self.pieces.is_empty()) {
+            (true, false) => TorrentVersion::Hybrid,
+            (true, true) => TorrentVersion::V2,
+            (false, _) => TorrentVersion::V1,
+        }
+    }
+
+    /// Check the info dict for unusual but valid values that often
+    /// point to a malformed torrent. Nothing reported here prevents
+    /// the torrent from being downloaded.
+    pub fn lint(&self) -> Vec<InfoWarning> {
+        let mut warnings = Vec::new();
+        if self.piece_length < MIN_PIECE_LENGTH {
+            warnings.push(InfoWarning::PieceLengthTooSmall(self.piece_length));
+        }
+        if !self.piece_length.is_power_of_two() {
+            warnings.push(InfoWarning::PieceLengthNotPowerOfTwo(self.piece_length));
+        }
+        warnings
+    }
+
+    fn from(dict: &Dict, info_bytes: Option<&[u8]>) -> Result<Self, BencodeError> {
+        let Bencode::Dict(info_dict) = get_value("info", dict)? else {
+            return Err(parsing_error("'info' in meta info must be a dictionary"));
+        };
+        Self::from_info_dict(info_dict, info_bytes)
+    }
+
+    /// The info dict is only re-encoded when its original bytes aren't given
+    fn from_info_dict(info_dict: &Dict, info_bytes: Option<&[u8]>) -> Result<Self, BencodeError> {
+        let Some(piece_length) = info_dict
+            .get(&ByteString::new("piece length"))
+            .and_then(Bencode::as_u64)
+        else {
+            return Err(missing_info_key("piece length"));
+        };
+
+        let pieces = match info_dict.get(&ByteString::new("pieces")) {
+            Some(Bencode::Text(pieces)) => pieces.clone(),
+            // v2-only torrents hash their files in the `file tree` instead
+            None if is_v2(info_dict) => ByteString::from_vec(Vec::new()),
+            _ => return Err(missing_info_key("pieces")),
+        };
+        check_pieces_len(&pieces)?;
+
+        let Some(Bencode::Text(_)) = info_dict.get(&ByteString::new("name")) else {
+            return Err(missing_info_key("name"));
+        };
+
+        let private = info_dict
+            .get(&ByteString::new("private"))
+            .map(|v| &Bencode::Number(1) == v)
+            .unwrap_or_else(|| false);
+        let file_info = Self::parse_file_info(info_dict)?;
+        let bencode_value = match info_bytes {
+            Some(info_bytes) => info_bytes.to_vec(),
+            None => BencodeParser::encode(&Bencode::Dict(info_dict.clone())),
+        };
+        let info = Self {
+            piece_length,
+            pieces,
+            private,
+            file_info,
+            bencode_value,
+            info_hash: OnceLock::new(),
+        };
+        // v2-only torrents hash their files in the `file tree`, not in pieces
+        if info.pieces.is_empty() && is_v2(info_dict) {
+            if info.checked_total_length().is_none() {
+                return Err(files_too_long());
+            }
+        } else {
+            info.validate_length()?;
+        }
+        Ok(info)
     }
 
     fn parse_file_info(dict: &Dict) -> Result<FileMode, BencodeError> {
@@ -134,6 +725,10 @@ impl Info {
                 let multi_file = MultiFile::from(dict)?;
                 Ok(FileMode::Multi(multi_file))
             }
+            // v2-only torrents only list their files in the `file tree`
+            None if !dict.contains_key(&ByteString::new("length")) && is_v2(dict) => {
+                Self::parse_file_tree(dict)
+            }
             // single-file mode
             None => {
                 let single_file = SingleFile::from(dict)?;
@@ -141,6 +736,84 @@ impl Info {
             }
         }
     }
+
+    /// Read the files of a v2 `file tree` (BEP 52), in tree order. Each path
+    /// component maps to a subtree, down to the files which map an empty key
+    /// to their `length`. A tree holding only a file named after the torrent
+    /// is a single-file torrent.
+    fn parse_file_tree(dict: &Dict) -> Result<FileMode, BencodeError> {
+        let (Some(Bencode::Text(name)), Some(Bencode::Dict(tree))) = (
+            dict.get(&ByteString::new("name")),
+            dict.get(&ByteString::new("file tree")),
+        ) else {
+            return Err(parsing_error("Invalid file tree"));
+        };
+
+        let mut files = Vec::new();
+        let mut subtrees = vec![(Vec::new(), tree)];
+        while let Some((path, subtree)) = subtrees.pop() {
+            if let (false, Some(file)) = (path.is_empty(), subtree.get(&ByteString::new(""))) {
+                let Some(length) = file.get("length").and_then(Bencode::as_u64) else {
+                    return Err(parsing_error("files in 'file tree' must have a length"));
+                };
+                files.push(MultiFileItem {
+                    length,
+                    md5sum: None,
+                    path,
+                });
+                continue;
+            }
+            // reversed, so they are popped in order
+            for (component, child) in subtree.iter().rev() {
+                let Bencode::Dict(child) = child else {
+                    return Err(parsing_error("'file tree' entries must be dictionaries"));
+                };
+                let mut child_path = path.clone();
+                child_path.push(component.to_string());
+                subtrees.push((child_path, child));
+            }
+        }
+
+        let name = name.to_string();
+        match files.as_slice() {
+            [file] if file.path == [name.as_str()] => Ok(FileMode::Single(SingleFile {
+                name,
+                length: file.length,
+                md5sum: None,
+            })),
+            _ => Ok(FileMode::Multi(MultiFile { name, files })),
+        }
+    }
+}
+
+/// Whether the info dict is the one of a v2 torrent (BEP 52)
+fn is_v2(info_dict: &Dict) -> bool {
+    info_dict.get(&ByteString::new("meta version")) == Some(&Bencode::Number(2))
+        && matches!(
+            info_dict.get(&ByteString::new("file tree")),
+            Some(Bencode::Dict(_))
+        )
+}
+
+/// `pieces` is a concatenation of 20-byte SHA1 hashes
+fn check_pieces_len(pieces: &ByteString) -> Result<(), BencodeError> {
+    if !pieces.len().is_multiple_of(PIECE_HASH_LEN) {
+        return Err(BencodeError::new(format!(
+            "pieces length {} is not a multiple of {}",
+            pieces.len(),
+            PIECE_HASH_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Build an [`Info`] from a decoded info dict
+impl TryFrom<&Bencode> for Info {
+    type Error = BencodeError;
+
+    fn try_from(value: &Bencode) -> Result<Self, Self::Error> {
+        Self::from_info_dict(expect_dict(value, "info")?, None)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -149,6 +822,15 @@ pub enum FileMode {
     Multi(MultiFile),
 }
 
+/// Read the file mode out of a decoded info dict
+impl TryFrom<&Bencode> for FileMode {
+    type Error = BencodeError;
+
+    fn try_from(value: &Bencode) -> Result<Self, Self::Error> {
+        Info::parse_file_info(expect_dict(value, "info")?)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct MultiFile {
     /// the name of the directory in which to store all the files.
@@ -184,6 +866,15 @@ impl MultiFile {
     }
 }
 
+/// Read the multi-file details out of a decoded info dict
+impl TryFrom<&Bencode> for MultiFile {
+    type Error = BencodeError;
+
+    fn try_from(value: &Bencode) -> Result<Self, Self::Error> {
+        Self::from(expect_dict(value, "info")?)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct MultiFileItem {
     pub length: u64,
@@ -203,10 +894,10 @@ pub struct MultiFileItem {
 impl MultiFileItem {
     fn from(dict: &Dict) -> Result<Self, BencodeError> {
         if let Some(path) = get_opt_str_list("path", dict) {
-            if let Bencode::Number(length) = get_value("length", dict)? {
+            if let Some(length) = get_value("length", dict)?.as_u64() {
                 let md5sum = get_optional_str("md5sum", dict);
                 return Ok(Self {
-                    length: *length,
+                    length,
                     path,
                     md5sum,
                 });
@@ -230,11 +921,11 @@ pub struct SingleFile {
 impl SingleFile {
     fn from(dict: &Dict) -> Result<Self, BencodeError> {
         if let Bencode::Text(name) = get_value("name", dict)? {
-            if let Bencode::Number(length) = get_value("length", dict)? {
+            if let Some(length) = get_value("length", dict)?.as_u64() {
                 let md5sum = get_optional_str("md5sum", dict);
                 return Ok(Self {
                     name: name.to_string(),
-                    length: *length,
+                    length,
                     md5sum,
                 });
             }
@@ -244,6 +935,83 @@ impl SingleFile {
     }
 }
 
+/// Read the single file details out of a decoded info dict
+impl TryFrom<&Bencode> for SingleFile {
+    type Error = BencodeError;
+
+    fn try_from(value: &Bencode) -> Result<Self, Self::Error> {
+        Self::from(expect_dict(value, "info")?)
+    }
+}
+
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed = replaced.trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        return String::from("_");
+    }
+
+    // Windows reserves device names regardless of the extension, e.g. `con.txt`
+    let stem = trimmed.split('.').next().unwrap_or(trimmed);
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return format!("_{}", trimmed);
+    }
+
+    trimmed.to_string()
+}
+
+/// Announce list tiers, dropping empty URLs, duplicate URLs and empty tiers
+fn parse_announce_list(dict: &Dict) -> Option<Vec<Vec<String>>> {
+    let Some(Bencode::List(list)) = dict.get(&ByteString::new("announce-list")) else {
+        return None;
+    };
+
+    let mut seen = Vec::new();
+    let tiers: Vec<Vec<String>> = list
+        .iter()
+        .filter_map(Bencode::as_list)
+        .map(|tier| {
+            tier.iter()
+                .filter_map(Bencode::as_text)
+                .map(trim_url)
+                .filter(|url| {
+                    if url.is_empty() || seen.contains(url) {
+                        return false;
+                    }
+                    seen.push(url.clone());
+                    true
+                })
+                .collect::<Vec<String>>()
+        })
+        .filter(|tier| !tier.is_empty())
+        .collect();
+    (!tiers.is_empty()).then_some(tiers)
+}
+
+/// Some torrents have tracker URLs with surrounding whitespace (e.g. a trailing
+/// newline), which would end up in the middle of the announce request URL.
+fn trim_url(url: &ByteString) -> String {
+    url.to_string()
+        .trim_matches(|c: char| c.is_ascii_whitespace())
+        .to_string()
+}
+
 fn get_opt_str_list(key: &str, dict: &Dict) -> Option<Vec<String>> {
     dict.get(&ByteString::new(key)).and_then(|v| match v {
         Bencode::List(list) => {
@@ -279,6 +1047,658 @@ fn get_value<'a>(key: &str, dict: &'a Dict) -> Result<&'a Bencode, BencodeError>
     }
 }
 
+fn expect_dict<'a>(value: &'a Bencode, name: &str) -> Result<&'a Dict, BencodeError> {
+    match value {
+        Bencode::Dict(dict) => Ok(dict),
+        _ => Err(BencodeError::new(format!(
+            "'{}' must be a dictionary",
+            name
+        ))),
+    }
+}
+
+fn missing_info_key(key: &str) -> BencodeError {
+    BencodeError::new(format!(
+        "missing or invalid required key '{}' in info dict",
+        key
+    ))
+}
+
+fn files_too_long() -> BencodeError {
+    BencodeError::new(format!("files add up to more than {} bytes", u64::MAX))
+}
+
 fn parsing_error(msg: &str) -> BencodeError {
     BencodeError::new(msg)
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn info_from(raw_info: &str) -> Info {
+        let raw = format!("d4:info{}e", raw_info);
+        let Bencode::Dict(dict) = BencodeParser::decode(raw.as_bytes()).unwrap() else {
+            panic!("expected a dict");
+        };
+        Info::from(&dict, None).unwrap()
+    }
+
+    #[test]
+    fn should_convert_in_memory_bencode_into_meta_info() {
+        let info = Bencode::Dict(IndexMap::from([
+            (ByteString::new("length"), Bencode::Number(10)),
+            (ByteString::new("name"), Bencode::Text(ByteString::new("x"))),
+            (ByteString::new("piece length"), Bencode::Number(16384)),
+            (
+                ByteString::new("pieces"),
+                Bencode::Text(ByteString::from_vec(vec![0; 20])),
+            ),
+        ]));
+        let torrent = Bencode::Dict(IndexMap::from([
+            (
+                ByteString::new("announce"),
+                Bencode::Text(ByteString::new("http://t/announce")),
+            ),
+            (ByteString::new("info"), info.clone()),
+        ]));
+
+        let meta_info = MetaInfo::try_from(&torrent).unwrap();
+        assert_eq!(meta_info.announce, "http://t/announce");
+        assert_eq!(meta_info.info.bencode_value, BencodeParser::encode(&info));
+
+        let expected_file = SingleFile {
+            name: String::from("x"),
+            length: 10,
+            md5sum: None,
+        };
+        assert_eq!(
+            FileMode::try_from(&info).unwrap(),
+            FileMode::Single(SingleFile::try_from(&info).unwrap())
+        );
+        assert_eq!(SingleFile::try_from(&info).unwrap(), expected_file);
+        assert_eq!(Info::try_from(&info).unwrap().piece_length, 16384);
+        assert!(MultiFile::try_from(&info).is_err());
+        assert!(Info::try_from(&Bencode::Number(1)).is_err());
+    }
+
+    #[test]
+    fn should_retain_non_utf8_info_dict_keys() {
+        let binary_key = ByteString::from_vec(vec![0xff, 0xfe, 0x00]);
+        let mut raw = b"d8:announce8:http://t4:infod3:".to_vec();
+        raw.extend(binary_key.iter());
+        raw.extend(
+            b"i1e6:lengthi10e4:name1:x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+        );
+
+        let from_bytes = MetaInfo::from_bytes(&raw).unwrap();
+        let from_bencode = MetaInfo::try_from(&BencodeParser::decode(&raw).unwrap()).unwrap();
+
+        for meta_info in [from_bytes, from_bencode] {
+            let Bencode::Dict(info) = BencodeParser::decode(&meta_info.info.bencode_value).unwrap()
+            else {
+                panic!("expected the info to be a dict");
+            };
+            assert_eq!(info.get(&binary_key), Some(&Bencode::Number(1)));
+        }
+    }
+
+    #[test]
+    fn should_sanitize_torrent_name() {
+        let info = info_from(
+            "d6:lengthi10e4:name8:con/../x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+        );
+
+        assert_eq!(info.name(), "con/../x");
+        assert_eq!(info.safe_name(), "con_.._x");
+    }
+
+    #[test]
+    fn should_add_and_remove_trackers_without_touching_the_info_hash() {
+        let mut meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        let info_bytes = meta_info.info.bencode_value.clone();
+        let original_tiers = meta_info.announce_list.clone().unwrap();
+
+        meta_info.add_tracker(0, "http://backup.example/announce");
+        meta_info.add_tracker(5, "udp://tracker.example:1337");
+
+        let mut expected_tiers = original_tiers.clone();
+        expected_tiers[0].push(String::from("http://backup.example/announce"));
+        expected_tiers.push(vec![String::from("udp://tracker.example:1337")]);
+        assert_eq!(meta_info.announce_list, Some(expected_tiers));
+        assert_eq!(meta_info.info.bencode_value, info_bytes);
+
+        assert!(meta_info.remove_tracker("udp://tracker.example:1337"));
+        assert!(meta_info.remove_tracker("http://backup.example/announce"));
+        assert!(!meta_info.remove_tracker("http://backup.example/announce"));
+        assert_eq!(meta_info.announce_list, Some(original_tiers.clone()));
+        assert_eq!(meta_info.info.bencode_value, info_bytes);
+
+        // trackers are only listed once, whatever the tier
+        meta_info.add_tracker(1, &original_tiers[0][0]);
+        assert_eq!(meta_info.announce_list, Some(original_tiers.clone()));
+
+        // the announce URL is removed too, not only its tier
+        assert_eq!(meta_info.announce, original_tiers[0][0]);
+        assert!(meta_info.remove_tracker(&original_tiers[0][0]));
+        assert_eq!(meta_info.announce, original_tiers[1][0]);
+        assert!(meta_info.remove_tracker(&original_tiers[1][0]));
+        assert_eq!(meta_info.announce, "");
+        assert_eq!(meta_info.announce_list, None);
+
+        meta_info.add_tracker(0, "http://backup.example/announce");
+        assert_eq!(
+            meta_info.announce_list,
+            Some(vec![vec![String::from("http://backup.example/announce")]])
+        );
+    }
+
+    #[test]
+    fn should_start_the_announce_list_from_the_announce_url() {
+        let raw = "d8:announce14:http://a/track4:infod6:lengthi10e4:name1:x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+        let mut meta_info = MetaInfo::from_bytes(raw.as_bytes()).unwrap();
+        assert_eq!(meta_info.announce_list, None);
+
+        meta_info.add_tracker(1, "http://b/track");
+        assert_eq!(
+            meta_info.announce_list,
+            Some(vec![
+                vec![String::from("http://a/track")],
+                vec![String::from("http://b/track")],
+            ])
+        );
+    }
+
+    #[test]
+    fn should_trim_whitespace_around_tracker_urls() {
+        let raw = "d8:announce18:http://t/announce\n13:announce-listll19: http://u/announce\tee4:infod6:lengthi10e4:name1:x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+        let meta_info = MetaInfo::from_bytes(raw.as_bytes()).unwrap();
+
+        assert_eq!(meta_info.announce, "http://t/announce");
+        assert_eq!(
+            meta_info.announce_list,
+            Some(vec![vec![String::from("http://u/announce")]])
+        );
+    }
+
+    #[test]
+    fn should_drop_empty_tiers_and_duplicate_tracker_urls() {
+        let raw = "d8:announce8:http://t13:announce-listll8:http://u0:elel2:  e\
+                   l8:http://t8:http://uee4:infod6:lengthi10e4:name1:x\
+                   12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+        let meta_info = MetaInfo::from_bytes(raw.as_bytes()).unwrap();
+
+        assert_eq!(
+            meta_info.announce_list,
+            Some(vec![
+                vec![String::from("http://u")],
+                vec![String::from("http://t")]
+            ])
+        );
+
+        let raw = "d8:announce8:http://t13:announce-listllee4:infod6:lengthi10e\
+                   4:name1:x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee";
+        let meta_info = MetaInfo::from_bytes(raw.as_bytes()).unwrap();
+        assert_eq!(meta_info.announce_list, None);
+    }
+
+    #[test]
+    fn should_name_the_missing_required_info_key() {
+        let cases = [
+            (
+                "piece length",
+                "d6:lengthi10e4:name1:x6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+            ),
+            ("pieces", "d6:lengthi10e4:name1:x12:piece lengthi16384ee"),
+            (
+                "name",
+                "d6:lengthi10e12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+            ),
+        ];
+
+        for (missing_key, raw_info) in cases {
+            let raw = format!("d4:info{}e", raw_info);
+            let Bencode::Dict(dict) = BencodeParser::decode(raw.as_bytes()).unwrap() else {
+                panic!("expected a dict");
+            };
+            let err = Info::from(&dict, None).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "missing or invalid required key '{}' in info dict",
+                    missing_key
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn should_prefix_reserved_windows_names() {
+        assert_eq!(sanitize_file_name("CON"), "_CON");
+        assert_eq!(sanitize_file_name("nul.txt"), "_nul.txt");
+        assert_eq!(sanitize_file_name(".."), "_");
+        assert_eq!(sanitize_file_name("movie. "), "movie");
+        assert_eq!(sanitize_file_name("a\0b\\c"), "a_b_c");
+    }
+
+    #[test]
+    fn should_warn_about_unusual_piece_lengths() {
+        let info = info_from(
+            "d6:lengthi10e4:name1:x12:piece lengthi1000e6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+        );
+        assert_eq!(
+            info.lint(),
+            vec![
+                InfoWarning::PieceLengthTooSmall(1000),
+                InfoWarning::PieceLengthNotPowerOfTwo(1000)
+            ]
+        );
+
+        let info = info_from(
+            "d6:lengthi10e4:name1:x12:piece lengthi262144e6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+        );
+        assert!(info.lint().is_empty());
+    }
+
+    #[test]
+    fn should_parse_v2_piece_layers() {
+        let pieces_root = vec![0xaa; 32];
+        let layer: Vec<u8> = [vec![0x11; 32], vec![0x22; 32]].concat();
+
+        let mut raw = b"d8:announce17:http://t/announce4:infod6:lengthi32768e4:name1:x12:piece lengthi16384e6:pieces40:".to_vec();
+        raw.extend([0; 40]);
+        raw.extend(b"e12:piece layersd32:");
+        raw.extend(&pieces_root);
+        raw.extend(b"64:");
+        raw.extend(&layer);
+        raw.extend(b"ee");
+
+        let meta_info = MetaInfo::from_bytes(&raw).unwrap();
+        let piece_layers = meta_info.piece_layers.unwrap();
+        assert_eq!(piece_layers.len(), 1);
+        assert_eq!(
+            piece_layers.get(&ByteString::from_vec(pieces_root)),
+            Some(&ByteString::from_vec(layer))
+        );
+    }
+
+    #[test]
+    fn should_reject_pieces_inconsistent_with_the_total_length() {
+        let raw = format!(
+            "d8:announce3:url4:infod6:lengthi40000e4:name1:x12:piece lengthi16384e6:pieces40:{}ee",
+            "a".repeat(40)
+        );
+        let err = MetaInfo::from_bytes(raw.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2 pieces of 16384 bytes cover 23616 bytes instead of the total length of 40000 bytes"
+        );
+
+        let info = info_from(&format!(
+            "d6:lengthi40000e4:name1:x12:piece lengthi16384e6:pieces60:{}e",
+            "a".repeat(60)
+        ));
+        assert_eq!(info.last_piece_length(), 40000 - 2 * 16384);
+        assert!(info.validate_length().is_ok());
+
+        let info = info_from("d6:lengthi32768e4:name1:x12:piece lengthi16384e6:pieces40:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaae");
+        assert_eq!(info.last_piece_length(), 16384);
+        assert!(info.validate_length().is_ok());
+    }
+
+    #[test]
+    fn should_reject_lengths_overflowing_u64() {
+        let max = i64::MAX;
+        let raw = format!("d8:announce3:url4:infod5:filesld6:lengthi{max}e4:pathl1:aeed6:lengthi{max}e4:pathl1:beed6:lengthi{max}e4:pathl1:ceee4:name1:x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaaee");
+        let err = MetaInfo::from_bytes(raw.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "files add up to more than 18446744073709551615 bytes"
+        );
+
+        let mut info = info_from(&format!(
+            "d6:lengthi{max}e4:name1:x12:piece lengthi{max}e6:pieces20:{}e",
+            "a".repeat(20)
+        ));
+        info.pieces = ByteString::from_vec(vec![b'a'; 60]);
+        assert_eq!(info.last_piece_length(), max as u64);
+        let err = info.validate_length().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("3 pieces of {max} bytes cover more than {} bytes", u64::MAX)
+        );
+    }
+
+    struct CountingSha1(std::cell::Cell<usize>);
+
+    impl Sha1Backend for CountingSha1 {
+        fn digest(&self, data: &[u8]) -> [u8; 20] {
+            self.0.set(self.0.get() + 1);
+            hash::sha1(data)
+        }
+    }
+
+    #[test]
+    fn should_compute_the_info_hash_once() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Info>();
+
+        let info = MetaInfo::from_file("tests/ubuntu_sample.torrent")
+            .unwrap()
+            .info;
+        let hasher = CountingSha1(std::cell::Cell::new(0));
+
+        let info_hash = info.info_hash_with(&hasher);
+        assert_eq!(info.info_hash_with(&hasher), info_hash);
+        assert_eq!(info.info_hash(), info_hash);
+        assert_eq!(hasher.0.get(), 1);
+    }
+
+    #[test]
+    fn should_write_the_original_bytes_unchanged() {
+        // keys aren't sorted, so re-encoding would reorder them
+        let raw = b"d4:infod6:lengthi10e4:name1:x6:pieces20:aaaaaaaaaaaaaaaaaaaa12:piece lengthi16384ee8:announce23:http://tracker.example/e";
+        let meta_info = MetaInfo::from_bytes(raw).unwrap();
+        assert_ne!(
+            BencodeParser::encode(&BencodeParser::decode(raw).unwrap()),
+            raw
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("rustorrent-unchanged-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        meta_info.write_unchanged(path).unwrap();
+        let written = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(written, raw);
+        assert_eq!(
+            MetaInfo::from_bytes(&written).unwrap().info.info_hash(),
+            meta_info.info.info_hash()
+        );
+
+        // no original bytes to write, the file mustn't be truncated
+        fs::write(path, raw).unwrap();
+        let from_bencode = MetaInfo::try_from(&BencodeParser::decode(raw).unwrap()).unwrap();
+        let err = from_bencode.write_unchanged(path).unwrap_err();
+        let written = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(written, raw);
+    }
+
+    #[test]
+    fn should_refuse_to_read_torrents_over_the_maximum_size() {
+        let err = MetaInfo::from_reader(io::repeat(b'0')).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::LimitExceeded);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "torrent exceeds the maximum of {} bytes",
+                DEFAULT_MAX_FILE_SIZE
+            )
+        );
+    }
+
+    #[test]
+    fn should_write_a_torrent_that_reads_back_the_same() {
+        let mut meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        meta_info.add_tracker(1, "udp://tracker.example:1337");
+        meta_info.comment = Some(String::from("round trip"));
+
+        let path = std::env::temp_dir().join(format!("rustorrent-to-file-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        meta_info.to_file(path).unwrap();
+        let written = MetaInfo::from_file(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(written.announce, meta_info.announce);
+        assert_eq!(written.announce_list, meta_info.announce_list);
+        assert_eq!(written.comment.as_deref(), Some("round trip"));
+        assert_eq!(written.created_by, meta_info.created_by);
+        assert_eq!(written.encoding, meta_info.encoding);
+        assert_eq!(written.creation_date, meta_info.creation_date);
+        assert_eq!(written.piece_layers, meta_info.piece_layers);
+        assert_eq!(written.info.name(), meta_info.info.name());
+        assert_eq!(written.info.file_info, meta_info.info.file_info);
+        assert_eq!(written.info.pieces, meta_info.info.pieces);
+        assert_eq!(written.info.info_hash(), meta_info.info.info_hash());
+    }
+
+    #[test]
+    fn should_write_the_info_dict_and_unknown_keys_as_they_were() {
+        // the info dict keys aren't sorted, so re-encoding it would change the info_hash
+        let raw = b"d8:announce23:http://tracker.example/4:infod6:lengthi10e4:name1:x6:pieces20:aaaaaaaaaaaaaaaaaaaa12:piece lengthi16384ee5:nodesll9:127.0.0.1i6881eee8:url-listl18:http://mirror/filee7:comment5:helloe";
+        let meta_info = MetaInfo::from_bytes(raw).unwrap();
+
+        let path = std::env::temp_dir().join(format!("rustorrent-to-bytes-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        meta_info.to_file(path).unwrap();
+        let written = MetaInfo::from_file(path).unwrap();
+        let written_bytes = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(written.info.info_hash(), meta_info.info.info_hash());
+        assert_eq!(written.info.bencode_value, meta_info.info.bencode_value);
+        assert_eq!(written.comment.as_deref(), Some("hello"));
+        let written = written.to_bencode();
+        let mirror = Bencode::Text(ByteString::new("http://mirror/file"));
+        assert_eq!(written.get("url-list"), Some(&Bencode::List(vec![mirror])));
+        assert!(written.get("nodes").is_some());
+        // top-level keys are written in canonical order
+        let Bencode::Dict(dict) = written else {
+            panic!("expected a dict");
+        };
+        let keys: Vec<String> = dict.keys().map(ByteString::to_string).collect();
+        assert_eq!(keys, ["announce", "comment", "info", "nodes", "url-list"]);
+        assert_eq!(
+            MetaInfo::from_bytes(&written_bytes).unwrap().to_bytes(),
+            written_bytes
+        );
+    }
+
+    #[test]
+    fn should_build_an_info_without_parsing() {
+        let file = |path: &str, length: u64| MultiFileItem {
+            length,
+            md5sum: None,
+            path: path.split('/').map(String::from).collect(),
+        };
+        let file_info = FileMode::Multi(MultiFile {
+            name: String::from("album"),
+            files: vec![file("cd1/01.flac", 30_000), file("cover.jpg", 10_000)],
+        });
+        let pieces = |count: usize| ByteString::from_vec(vec![b'a'; count * PIECE_HASH_LEN]);
+        let info = Info::new(16384, pieces(3), file_info, false).unwrap();
+
+        assert_eq!(info.name(), "album");
+        assert_eq!(info.total_length(), 40_000);
+        assert_eq!(info.num_pieces(), 3);
+        assert_eq!(info.last_piece_length(), 40_000 - 2 * 16384);
+
+        // the info dict is encoded, so the info_hash is the one of a parsed torrent
+        let mut raw = b"d8:announce23:http://tracker.example/4:info".to_vec();
+        raw.extend(&info.bencode_value);
+        raw.push(b'e');
+        let parsed = MetaInfo::from_bytes(&raw).unwrap().info;
+        assert_eq!(parsed.total_length(), 40_000);
+        assert_eq!(parsed.info_hash(), info.info_hash());
+
+        let single = |length| {
+            FileMode::Single(SingleFile {
+                name: String::from("x"),
+                length,
+                md5sum: None,
+            })
+        };
+        assert!(Info::new(16384, pieces(2), single(40_000), false).is_err());
+        assert!(Info::new(16384, ByteString::new("abc"), single(16_384), false).is_err());
+        assert!(Info::new(16384, pieces(1), single(16_384), true).is_ok());
+    }
+
+    #[test]
+    fn should_list_the_pieces_of_each_file() {
+        let file = |length: u64| MultiFileItem {
+            length,
+            md5sum: None,
+            path: vec![format!("{}.bin", length)],
+        };
+        // pieces of 10 bytes: [0, 10) [10, 20) [20, 30) [30, 40) [40, 45)
+        let file_info = FileMode::Multi(MultiFile {
+            name: String::from("files"),
+            files: vec![file(15), file(0), file(5), file(25)],
+        });
+        let pieces = ByteString::from_vec(vec![b'a'; 5 * PIECE_HASH_LEN]);
+        let info = Info::new(10, pieces, file_info, false).unwrap();
+
+        assert_eq!(info.pieces_for_file(0), Some(0..=1));
+        assert_eq!(info.pieces_for_file(1), None);
+        // shares piece 1 with the first file
+        assert_eq!(info.pieces_for_file(2), Some(1..=1));
+        assert_eq!(info.pieces_for_file(3), Some(2..=4));
+        assert_eq!(info.pieces_for_file(4), None);
+
+        let mut info = info;
+        info.file_info = FileMode::Multi(MultiFile {
+            name: String::from("files"),
+            files: vec![file(u64::MAX), file(10), file(u64::MAX / 2)],
+        });
+        assert_eq!(info.pieces_for_file(1), None);
+        assert_eq!(info.pieces_for_file(2), None);
+
+        let meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        let num_pieces = meta_info.info.num_pieces();
+        assert_eq!(meta_info.info.pieces_for_file(0), Some(0..=num_pieces - 1));
+    }
+
+    #[test]
+    fn should_reject_pieces_not_made_of_whole_hashes() {
+        for pieces in ["a".repeat(19), "a".repeat(41)] {
+            let raw = format!(
+                "d8:announce8:http://t4:infod6:lengthi10e4:name1:x\
+                 12:piece lengthi16384e6:pieces{}:{}ee",
+                pieces.len(),
+                pieces
+            );
+            let err = MetaInfo::from_bytes(raw.as_bytes()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("pieces length {} is not a multiple of 20", pieces.len())
+            );
+        }
+    }
+
+    #[test]
+    fn should_tell_the_torrent_format_version() {
+        let meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        assert_eq!(meta_info.info.format_version(), TorrentVersion::V1);
+
+        let torrent = |pieces: &str| {
+            let raw = format!(
+                "d8:announce8:http://t4:infod9:file treed1:xd0:d6:lengthi10eeee\
+                 6:lengthi10e12:meta versioni2e4:name1:x12:piece lengthi16384e6:pieces{}ee",
+                pieces
+            );
+            MetaInfo::from_bytes(raw.as_bytes()).unwrap().info
+        };
+        let hybrid = torrent("20:aaaaaaaaaaaaaaaaaaaa");
+        assert_eq!(hybrid.format_version(), TorrentVersion::Hybrid);
+
+        // v2-only torrents have neither `pieces` nor `length`
+        let raw = format!(
+            "d8:announce8:http://t4:infod9:file treed1:xd0:d6:lengthi10e11:pieces root32:{}eee\
+             12:meta versioni2e4:name1:x12:piece lengthi16384eee",
+            "r".repeat(32)
+        );
+        let v2 = MetaInfo::from_bytes(raw.as_bytes()).unwrap().info;
+        assert_eq!(v2.format_version(), TorrentVersion::V2);
+        assert_eq!(v2.num_pieces(), 0);
+        assert_eq!(
+            v2.file_info,
+            FileMode::Single(SingleFile {
+                name: String::from("x"),
+                length: 10,
+                md5sum: None,
+            })
+        );
+    }
+
+    #[test]
+    fn should_list_the_files_of_a_v2_file_tree() {
+        let raw = "d8:announce8:http://t4:infod9:file treed\
+                   3:dird1:bd0:d6:lengthi2eee1:cd0:d6:lengthi3eeee\
+                   1:ad0:d6:lengthi1eeee\
+                   12:meta versioni2e4:name5:files12:piece lengthi16384eee";
+        let info = MetaInfo::from_bytes(raw.as_bytes()).unwrap().info;
+
+        let FileMode::Multi(multi) = &info.file_info else {
+            panic!("expected a multi-file torrent");
+        };
+        assert_eq!(multi.name, "files");
+        let files: Vec<(String, u64)> = multi
+            .files
+            .iter()
+            .map(|file| (file.path.join("/"), file.length))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (String::from("dir/b"), 2),
+                (String::from("dir/c"), 3),
+                (String::from("a"), 1)
+            ]
+        );
+        assert_eq!(info.total_length(), 6);
+
+        let missing_length = raw.replace("d6:lengthi1ee", "de");
+        assert!(MetaInfo::from_bytes(missing_length.as_bytes()).is_err());
+        // without `meta version` 2, `pieces` is still required
+        let v1 = raw.replace("12:meta versioni2e", "");
+        assert_eq!(
+            MetaInfo::from_bytes(v1.as_bytes()).unwrap_err().to_string(),
+            "missing or invalid required key 'pieces' in info dict"
+        );
+    }
+
+    #[test]
+    fn should_verify_pieces_against_their_hash() {
+        let blocks: [&[u8]; 3] = [b"first piece", b"second piece", b"last"];
+        let pieces: Vec<u8> = blocks.iter().flat_map(|block| hash::sha1(block)).collect();
+        let file_info = FileMode::Single(SingleFile {
+            name: String::from("x"),
+            length: 2 * 16 + 4,
+            md5sum: None,
+        });
+        let mut info = Info::new(16, ByteString::from_vec(pieces), file_info, false).unwrap();
+
+        assert_eq!(
+            info.piece_hash_iter().nth(1),
+            Some(&hash::sha1(blocks[1])[..])
+        );
+        assert!(info.verify_piece(0, blocks[0]));
+        assert!(info.verify_piece(2, blocks[2]));
+        assert!(!info.verify_piece(0, blocks[1]));
+        assert!(!info.verify_piece(2, b"last piece"));
+        assert!(!info.verify_piece(3, blocks[0]));
+
+        info.pieces = ByteString::from_vec(hash::sha1(blocks[0])[..19].to_vec());
+        assert!(!info.verify_piece(0, blocks[0]));
+    }
+
+    #[test]
+    fn should_reject_hostile_nesting_in_the_info_dict() {
+        const DEPTH: usize = 1_000_000;
+        let mut raw = b"d8:announce3:url4:infod6:lengthi1e4:name1:a5:depth".to_vec();
+        raw.extend(vec![b'l'; DEPTH]);
+        raw.extend(vec![b'e'; DEPTH]);
+        raw.extend(b"12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaaee");
+
+        let err = MetaInfo::from_bytes(&raw).unwrap_err();
+        assert_eq!(err.kind(), BencodeErrorKind::LimitExceeded);
+        assert_eq!(
+            err.to_string(),
+            "nesting exceeds the maximum depth of 100 at byte 148"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::parser::bencode::{
+    Bencode, BencodeError, BencodeErrorKind, BencodeParser, DecodeOptions,
+};
+
+/// Receives parsing events, e.g. to feed a metrics backend
+/// and chart how often tracker responses fail to parse, and why.
+pub trait ParseMetrics {
+    fn record_error(&self, kind: BencodeErrorKind);
+}
+
+/// In-memory counters of parse errors, one per [`BencodeErrorKind`].
+/// Safe to share across tasks.
+#[derive(Debug, Default)]
+pub struct ErrorCounters {
+    invalid: AtomicU64,
+    truncated: AtomicU64,
+    limit_exceeded: AtomicU64,
+    io: AtomicU64,
+}
+
+impl ErrorCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of errors recorded so far for the given kind
+    pub fn get(&self, kind: BencodeErrorKind) -> u64 {
+        self.counter(kind).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, kind: BencodeErrorKind) -> &AtomicU64 {
+        match kind {
+            BencodeErrorKind::Invalid => &self.invalid,
+            BencodeErrorKind::Truncated => &self.truncated,
+            BencodeErrorKind::LimitExceeded => &self.limit_exceeded,
+            BencodeErrorKind::Io => &self.io,
+        }
+    }
+}
+
+impl ParseMetrics for ErrorCounters {
+    fn record_error(&self, kind: BencodeErrorKind) {
+        self.counter(kind).fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl BencodeParser {
+    /// Same as [`BencodeParser::decode_with_options`], reporting
+    /// the kind of any error to the given metrics.
+    pub fn decode_with_metrics(
+        raw_content: &[u8],
+        options: &DecodeOptions,
+        metrics: &dyn ParseMetrics,
+    ) -> Result<Bencode, BencodeError> {
+        Self::decode_with_options(raw_content, options)
+            .inspect_err(|err| metrics.record_error(err.kind()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_count_errors_by_kind() {
+        let counters = ErrorCounters::new();
+        let options = DecodeOptions::default();
+
+        assert!(BencodeParser::decode_with_metrics(b"d8:interval", &options, &counters).is_err());
+        assert!(BencodeParser::decode_with_metrics(b"l5:abce", &options, &counters).is_err());
+        assert!(BencodeParser::decode_with_metrics(b"x", &options, &counters).is_err());
+        assert!(BencodeParser::decode_with_metrics(b"i1e", &options, &counters).is_ok());
+
+        assert_eq!(counters.get(BencodeErrorKind::Truncated), 2);
+        assert_eq!(counters.get(BencodeErrorKind::Invalid), 1);
+        assert_eq!(counters.get(BencodeErrorKind::LimitExceeded), 0);
+    }
+}
@@ -0,0 +1,248 @@
+use indexmap::IndexMap;
+
+use super::bencode::Bencode;
+use super::byte_string::ByteString;
+
+type Dict = IndexMap<ByteString, Bencode>;
+
+/// A mismatch between a bencode value and the expected torrent schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaIssue {
+    /// Location of the offending value, e.g. `info.files[2].path`
+    pub path: String,
+    /// The kind of value the schema expects at that location
+    pub expected: &'static str,
+    /// The kind of value found instead, or `missing`
+    pub found: &'static str,
+}
+
+impl Bencode {
+    /// Check this value against the metainfo (.torrent) schema:
+    /// required keys and the type of the value behind each known key.
+    ///
+    /// Unlike [`crate::parser::meta_info::MetaInfo`] parsing, this doesn't stop
+    /// at the first problem, it reports every issue found.
+    pub fn validate_torrent(&self) -> Vec<SchemaIssue> {
+        let mut validator = Validator::default();
+        validator.validate_root(self);
+        validator.issues
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Number,
+    List,
+    Dict,
+}
+
+impl Kind {
+    fn of(value: &Bencode) -> Self {
+        match value {
+            Bencode::Text(_) => Kind::Text,
+            Bencode::Number(_) => Kind::Number,
+            Bencode::List(_) => Kind::List,
+            Bencode::Dict(_) => Kind::Dict,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Text => "text",
+            Kind::Number => "number",
+            Kind::List => "list",
+            Kind::Dict => "dict",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    issues: Vec<SchemaIssue>,
+}
+
+impl Validator {
+    fn validate_root(&mut self, root: &Bencode) {
+        let Some(Bencode::Dict(root)) = self.check(Some(root), "", Kind::Dict, true) else {
+            return;
+        };
+
+        self.key(root, "", "announce", Kind::Text, true);
+        self.key(root, "", "creation date", Kind::Number, false);
+        self.key(root, "", "comment", Kind::Text, false);
+        self.key(root, "", "created by", Kind::Text, false);
+        self.key(root, "", "encoding", Kind::Text, false);
+
+        if let Some(Bencode::List(tiers)) = self.key(root, "", "announce-list", Kind::List, false) {
+            for (index, tier) in tiers.iter().enumerate() {
+                let tier_path = format!("announce-list[{}]", index);
+                if let Some(Bencode::List(urls)) =
+                    self.check(Some(tier), &tier_path, Kind::List, true)
+                {
+                    self.text_list(urls, &tier_path);
+                }
+            }
+        }
+
+        if let Some(Bencode::Dict(info)) = self.key(root, "", "info", Kind::Dict, true) {
+            self.validate_info(info);
+        }
+    }
+
+    fn validate_info(&mut self, info: &Dict) {
+        let path = "info";
+        self.key(info, path, "piece length", Kind::Number, true);
+        self.key(info, path, "pieces", Kind::Text, true);
+        self.key(info, path, "name", Kind::Text, true);
+        self.key(info, path, "private", Kind::Number, false);
+
+        if info.contains_key(&ByteString::new("files")) {
+            let Some(Bencode::List(files)) = self.key(info, path, "files", Kind::List, true) else {
+                return;
+            };
+            for (index, file) in files.iter().enumerate() {
+                let file_path = format!("info.files[{}]", index);
+                if let Some(Bencode::Dict(file)) =
+                    self.check(Some(file), &file_path, Kind::Dict, true)
+                {
+                    self.key(file, &file_path, "length", Kind::Number, true);
+                    self.key(file, &file_path, "md5sum", Kind::Text, false);
+                    if let Some(Bencode::List(segments)) =
+                        self.key(file, &file_path, "path", Kind::List, true)
+                    {
+                        self.text_list(segments, &format!("{}.path", file_path));
+                    }
+                }
+            }
+        } else {
+            self.key(info, path, "length", Kind::Number, true);
+            self.key(info, path, "md5sum", Kind::Text, false);
+        }
+    }
+
+    fn text_list(&mut self, values: &[Bencode], path: &str) {
+        for (index, value) in values.iter().enumerate() {
+            self.check(
+                Some(value),
+                &format!("{}[{}]", path, index),
+                Kind::Text,
+                true,
+            );
+        }
+    }
+
+    /// Check the value behind `key` in the given dict
+    fn key<'a>(
+        &mut self,
+        dict: &'a Dict,
+        parent_path: &str,
+        key: &str,
+        expected: Kind,
+        required: bool,
+    ) -> Option<&'a Bencode> {
+        let path = if parent_path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", parent_path, key)
+        };
+        self.check(dict.get(&ByteString::new(key)), &path, expected, required)
+    }
+
+    /// Record an issue if the value is missing (when required) or of the wrong kind.
+    /// Returns the value only when it matches the expected kind.
+    fn check<'a>(
+        &mut self,
+        value: Option<&'a Bencode>,
+        path: &str,
+        expected: Kind,
+        required: bool,
+    ) -> Option<&'a Bencode> {
+        match value {
+            Some(value) if Kind::of(value) == expected => Some(value),
+            Some(value) => {
+                self.issue(path, expected, Kind::of(value).name());
+                None
+            }
+            None => {
+                if required {
+                    self.issue(path, expected, "missing");
+                }
+                None
+            }
+        }
+    }
+
+    fn issue(&mut self, path: &str, expected: Kind, found: &'static str) {
+        self.issues.push(SchemaIssue {
+            path: path.to_string(),
+            expected: expected.name(),
+            found,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parser::bencode::BencodeParser;
+
+    use super::*;
+
+    #[test]
+    fn should_accept_a_valid_torrent() {
+        let torrent = BencodeParser::from_file("tests/ubuntu_sample.torrent").unwrap();
+        assert_eq!(torrent.validate_torrent(), vec![]);
+    }
+
+    #[test]
+    fn should_report_piece_length_with_the_wrong_type() {
+        let torrent = BencodeParser::decode(
+            b"d8:announce9:http://t/4:infod6:lengthi10e4:name1:x12:piece length5:163846:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+        )
+        .unwrap();
+
+        assert_eq!(
+            torrent.validate_torrent(),
+            vec![SchemaIssue {
+                path: String::from("info.piece length"),
+                expected: "number",
+                found: "text",
+            }]
+        );
+    }
+
+    #[test]
+    fn should_report_every_issue_in_multi_file_torrents() {
+        let torrent = BencodeParser::decode(
+            b"d4:infod5:filesld4:pathl1:ai1eeed6:lengthi1eee4:name1:x12:piece lengthi1e6:pieces0:ee",
+        )
+        .unwrap();
+
+        assert_eq!(
+            torrent.validate_torrent(),
+            vec![
+                SchemaIssue {
+                    path: String::from("announce"),
+                    expected: "text",
+                    found: "missing",
+                },
+                SchemaIssue {
+                    path: String::from("info.files[0].length"),
+                    expected: "number",
+                    found: "missing",
+                },
+                SchemaIssue {
+                    path: String::from("info.files[0].path[1]"),
+                    expected: "text",
+                    found: "number",
+                },
+                SchemaIssue {
+                    path: String::from("info.files[1].path"),
+                    expected: "list",
+                    found: "missing",
+                },
+            ]
+        );
+    }
+}
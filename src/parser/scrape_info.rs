@@ -0,0 +1,106 @@
+use indexmap::IndexMap;
+
+use crate::parser::announce_info::AnnounceError;
+use crate::parser::bencode::Bencode;
+use crate::parser::byte_string::ByteString;
+
+/// Stats of a torrent returned by a tracker scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrapeInfo {
+    /// number of seeders
+    pub complete: u64,
+    /// number of times the torrent was completely downloaded
+    pub downloaded: u64,
+    /// number of leechers
+    pub incomplete: u64,
+}
+
+impl ScrapeInfo {
+    /// Parse a scrape response: the stats of each torrent are under `files`,
+    /// keyed by the raw 20-byte info_hash.
+    pub fn parse_files(value: &Bencode) -> Result<IndexMap<[u8; 20], Self>, AnnounceError> {
+        let malformed = |msg: String| Err(AnnounceError::MalformedResponse(msg));
+
+        let Bencode::Dict(map) = value else {
+            return malformed(String::from("scrape response is not a dict"));
+        };
+        if let Some(reason) = map.get(&ByteString::new("failure reason")) {
+            return Err(AnnounceError::TrackerFailure(match reason {
+                Bencode::Text(reason) => reason.to_string(),
+                other => format!("{:?}", other),
+            }));
+        }
+        let Some(files) = map
+            .get(&ByteString::new("files"))
+            .and_then(Bencode::as_dict)
+        else {
+            return malformed(String::from("scrape response has no \"files\" dict"));
+        };
+
+        let mut stats = IndexMap::with_capacity(files.len());
+        for (info_hash, value) in files {
+            let Ok(info_hash) = <[u8; 20]>::try_from(&info_hash[..]) else {
+                return malformed(format!(
+                    "scrape \"files\" key of {} bytes is not an info_hash",
+                    info_hash.len()
+                ));
+            };
+            let count = |key: &str| value.get(key).and_then(Bencode::as_u64);
+            let (Some(complete), Some(downloaded), Some(incomplete)) =
+                (count("complete"), count("downloaded"), count("incomplete"))
+            else {
+                return malformed(format!("invalid scrape stats: {:?}", value));
+            };
+            stats.insert(
+                info_hash,
+                Self {
+                    complete,
+                    downloaded,
+                    incomplete,
+                },
+            );
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::bencode::BencodeParser;
+
+    use super::*;
+
+    #[test]
+    fn should_parse_the_stats_of_each_torrent() {
+        let mut raw = b"d5:filesd20:".to_vec();
+        raw.extend([1; 20]);
+        raw.extend(b"d8:completei5e10:downloadedi50e10:incompletei10ee20:");
+        raw.extend([2; 20]);
+        raw.extend(b"d8:completei0e10:downloadedi0e10:incompletei1eeee");
+        let value = BencodeParser::decode(&raw).unwrap();
+
+        let stats = ScrapeInfo::parse_files(&value).unwrap();
+        assert_eq!(
+            stats.get(&[1; 20]),
+            Some(&ScrapeInfo {
+                complete: 5,
+                downloaded: 50,
+                incomplete: 10
+            })
+        );
+        assert_eq!(stats.get(&[2; 20]).unwrap().incomplete, 1);
+    }
+
+    #[test]
+    fn should_reject_malformed_scrape_responses() {
+        let failure = BencodeParser::decode(b"d14:failure reason9:forbiddene").unwrap();
+        assert_eq!(
+            ScrapeInfo::parse_files(&failure).unwrap_err().to_string(),
+            "tracker failure: forbidden"
+        );
+
+        let short_hash = BencodeParser::decode(b"d5:filesd3:abcd8:completei0eeee").unwrap();
+        assert!(ScrapeInfo::parse_files(&short_hash).is_err());
+        assert!(ScrapeInfo::parse_files(&Bencode::Number(1)).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use indexmap::IndexMap;
+
+use super::bencode::Bencode;
+use super::byte_string::ByteString;
+
+/// Hooks called by [`walk`] for every value of a bencode tree.
+/// Values are handed out mutably, so a visitor can rewrite them in place
+/// (e.g. redact passkeys) as well as inspect them.
+/// Every hook does nothing by default.
+pub trait Visitor {
+    /// Text values, dict keys aren't visited as they can be reached from [`Visitor::visit_dict`]
+    fn visit_text(&mut self, _text: &mut ByteString) {}
+
+    fn visit_number(&mut self, _number: &mut i64) {}
+
+    /// Called before walking the items of the list
+    fn visit_list(&mut self, _list: &mut Vec<Bencode>) {}
+
+    /// Called before walking the values of the dict
+    fn visit_dict(&mut self, _dict: &mut IndexMap<ByteString, Bencode>) {}
+}
+
+/// Walk the value depth-first, calling the visitor hook matching every value.
+/// Containers are visited before their contents, so changes made by
+/// [`Visitor::visit_list`] or [`Visitor::visit_dict`] are walked as well.
+pub fn walk<V: Visitor + ?Sized>(value: &mut Bencode, visitor: &mut V) {
+    match value {
+        Bencode::Text(text) => visitor.visit_text(text),
+        Bencode::Number(number) => visitor.visit_number(number),
+        Bencode::List(list) => {
+            visitor.visit_list(list);
+            for item in list.iter_mut() {
+                walk(item, visitor);
+            }
+        }
+        Bencode::Dict(dict) => {
+            visitor.visit_dict(dict);
+            for item in dict.values_mut() {
+                walk(item, visitor);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::bencode::BencodeParser;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TextCounter {
+        count: usize,
+    }
+
+    impl Visitor for TextCounter {
+        fn visit_text(&mut self, _text: &mut ByteString) {
+            self.count += 1;
+        }
+    }
+
+    struct Redact(&'static str);
+
+    impl Visitor for Redact {
+        fn visit_dict(&mut self, dict: &mut IndexMap<ByteString, Bencode>) {
+            if let Some(value) = dict.get_mut(&ByteString::new(self.0)) {
+                *value = Bencode::Text(ByteString::new("<redacted>"));
+            }
+        }
+    }
+
+    #[test]
+    fn should_count_every_text_value() {
+        let mut value =
+            BencodeParser::decode(b"d8:announce3:url4:infod5:filesl3:one3:twoe6:lengthi3eee")
+                .unwrap();
+
+        let mut counter = TextCounter::default();
+        walk(&mut value, &mut counter);
+        assert_eq!(counter.count, 3);
+    }
+
+    #[test]
+    fn should_redact_a_key_at_any_depth() {
+        let mut value =
+            BencodeParser::decode(b"d4:infold7:passkeyi42eee4:name7:passkey7:passkey3:abce")
+                .unwrap();
+
+        walk(&mut value, &mut Redact("passkey"));
+        assert_eq!(
+            BencodeParser::encode(&value),
+            b"d4:infold7:passkey10:<redacted>ee4:name7:passkey7:passkey10:<redacted>e"
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod bitfield;
+pub mod extension;
+pub mod handshake;
+pub mod interest;
+pub mod message;
+pub mod peer_id;
@@ -0,0 +1,93 @@
+/// Which pieces a peer has, one bit per piece.
+/// The first byte holds pieces 0 to 7, with the high bit being piece 0,
+/// as in the `bitfield` peer message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitfield {
+    bytes: Vec<u8>,
+    num_pieces: usize,
+}
+
+impl Bitfield {
+    /// An empty bitfield: no piece is set
+    pub fn new(num_pieces: usize) -> Self {
+        Self {
+            bytes: vec![0; num_pieces.div_ceil(8)],
+            num_pieces,
+        }
+    }
+
+    /// Build a bitfield from the payload of a `bitfield` message.
+    /// Returns `None` if the payload doesn't match the number of pieces,
+    /// including when spare bits at the end are set.
+    pub fn from_bytes(bytes: &[u8], num_pieces: usize) -> Option<Self> {
+        if bytes.len() != num_pieces.div_ceil(8) {
+            return None;
+        }
+        let bitfield = Self {
+            bytes: bytes.to_vec(),
+            num_pieces,
+        };
+        let spare_bits_set = (num_pieces..bytes.len() * 8).any(|index| bitfield.bit(index));
+        (!spare_bits_set).then_some(bitfield)
+    }
+
+    pub fn has(&self, index: usize) -> bool {
+        index < self.num_pieces && self.bit(index)
+    }
+
+    /// Set the bit of the given piece, returning whether it wasn't set before
+    pub fn set(&mut self, index: usize) -> bool {
+        if index >= self.num_pieces || self.bit(index) {
+            return false;
+        }
+        self.bytes[index / 8] |= 0x80 >> (index % 8);
+        true
+    }
+
+    /// Indices of the pieces set, in ascending order
+    pub fn iter_set(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_pieces).filter(|&index| self.bit(index))
+    }
+
+    pub fn count(&self) -> usize {
+        self.iter_set().count()
+    }
+
+    pub fn num_pieces(&self) -> usize {
+        self.num_pieces
+    }
+
+    /// Payload of the `bitfield` message for these pieces
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.bytes[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_use_the_high_bit_for_the_first_piece() {
+        let mut bitfield = Bitfield::new(10);
+        assert!(bitfield.set(0));
+        assert!(bitfield.set(9));
+        assert!(!bitfield.set(9));
+        assert!(!bitfield.set(10));
+
+        assert_eq!(bitfield.as_bytes(), &[0b1000_0000, 0b0100_0000]);
+        assert_eq!(bitfield.iter_set().collect::<Vec<_>>(), vec![0, 9]);
+    }
+
+    #[test]
+    fn should_reject_bitfields_with_spare_bits_set() {
+        assert!(Bitfield::from_bytes(&[0xff, 0b1100_0000], 10).is_some());
+        assert!(Bitfield::from_bytes(&[0xff, 0b1110_0000], 10).is_none());
+        assert!(Bitfield::from_bytes(&[0xff], 10).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use indexmap::IndexMap;
+
+use crate::parser::bencode::{Bencode, BencodeError, BencodeParser};
+use crate::parser::byte_string::ByteString;
+
+/// Id of the extended handshake among the extension protocol messages (BEP 10)
+pub const EXTENDED_HANDSHAKE_ID: u8 = 0;
+
+/// What a peer tells about itself in its extended handshake (BEP 10)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// Client name and version (`v`), e.g. "µTorrent 3.5.5"
+    pub client_version: Option<String>,
+    /// Local TCP listen port of the peer (`p`)
+    pub listen_port: Option<u16>,
+    /// Extension names supported by the peer, mapped to
+    /// the message id to use when sending them (`m`)
+    pub extensions: IndexMap<String, u8>,
+}
+
+impl PeerInfo {
+    /// Decode the bencoded payload of an extended handshake message
+    pub fn from_extended_handshake(payload: &[u8]) -> Result<Self, BencodeError> {
+        let Bencode::Dict(dict) = BencodeParser::decode(payload)? else {
+            return Err(BencodeError::new("extended handshake must be a dict"));
+        };
+
+        let client_version = match dict.get(&ByteString::new("v")) {
+            Some(Bencode::Text(version)) => Some(version.to_string()),
+            _ => None,
+        };
+
+        let listen_port = match dict.get(&ByteString::new("p")) {
+            Some(Bencode::Number(port)) => u16::try_from(*port).ok(),
+            _ => None,
+        };
+
+        let mut extensions = IndexMap::new();
+        if let Some(Bencode::Dict(m)) = dict.get(&ByteString::new("m")) {
+            for (name, id) in m {
+                // ids out of range are invalid, 0 means the extension is disabled
+                if let Bencode::Number(id) = id {
+                    if let Ok(id @ 1..) = u8::try_from(*id) {
+                        extensions.insert(name.to_string(), id);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            client_version,
+            listen_port,
+            extensions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::peer::message::Message;
+
+    use super::*;
+
+    #[test]
+    fn should_extract_the_client_version_from_the_extended_handshake() {
+        let payload = "d1:md11:ut_metadatai3e6:ut_pexi0ee1:pi6881e1:v15:µTorrent 3.5.5e";
+        let mut bytes = vec![0, 0, 0, 0, 20, EXTENDED_HANDSHAKE_ID];
+        bytes.extend(payload.as_bytes());
+        bytes[3] = (bytes.len() - 4) as u8;
+
+        let Message::Extended { id, payload } = Message::decode(&bytes[4..]).unwrap() else {
+            panic!("expected an extended message");
+        };
+        assert_eq!(id, EXTENDED_HANDSHAKE_ID);
+
+        let peer_info = PeerInfo::from_extended_handshake(&payload).unwrap();
+        assert_eq!(peer_info.client_version.as_deref(), Some("µTorrent 3.5.5"));
+        assert_eq!(peer_info.listen_port, Some(6881));
+        assert_eq!(peer_info.extensions.get("ut_metadata"), Some(&3));
+        assert_eq!(peer_info.extensions.get("ut_pex"), None);
+    }
+
+    #[test]
+    fn should_allow_handshakes_without_a_client_version() {
+        let peer_info = PeerInfo::from_extended_handshake(b"d1:mdee").unwrap();
+        assert_eq!(peer_info.client_version, None);
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::{self, ErrorKind};
+
+/// Protocol identifier sent at the start of every handshake
+pub const PROTOCOL: &[u8; 19] = b"BitTorrent protocol";
+
+/// Length of an encoded handshake:
+/// pstrlen, pstr, reserved bytes, info_hash and peer_id
+pub const HANDSHAKE_LEN: usize = 1 + 19 + 8 + 20 + 20;
+
+/// First message exchanged with a peer, telling which torrent we want
+/// and who we are. Unlike the other messages, it has no length prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    /// bits advertising protocol extensions, e.g. BEP 10
+    pub reserved: [u8; 8],
+    pub info_hash: [u8; 20],
+    pub peer_id: [u8; 20],
+}
+
+impl Handshake {
+    /// Handshake without any extension bit set
+    pub fn new(info_hash: [u8; 20], peer_id: [u8; 20]) -> Self {
+        Self {
+            reserved: [0; 8],
+            info_hash,
+            peer_id,
+        }
+    }
+
+    /// Whether the extension protocol (BEP 10) bit is set
+    pub fn supports_extension_protocol(&self) -> bool {
+        self.reserved[5] & 0x10 != 0
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HANDSHAKE_LEN);
+        bytes.push(PROTOCOL.len() as u8);
+        bytes.extend(PROTOCOL);
+        bytes.extend(self.reserved);
+        bytes.extend(self.info_hash);
+        bytes.extend(self.peer_id);
+        bytes
+    }
+
+    /// Decode a handshake, which must be exactly [`HANDSHAKE_LEN`] bytes long
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() != HANDSHAKE_LEN {
+            return Err(invalid_data(format!(
+                "handshake of {} bytes instead of {}",
+                bytes.len(),
+                HANDSHAKE_LEN
+            )));
+        }
+        if bytes[0] as usize != PROTOCOL.len() || &bytes[1..20] != PROTOCOL {
+            return Err(invalid_data(format!(
+                "handshake for an unknown protocol (pstrlen {})",
+                bytes[0]
+            )));
+        }
+
+        let mut handshake = Self::new([0; 20], [0; 20]);
+        handshake.reserved.copy_from_slice(&bytes[20..28]);
+        handshake.info_hash.copy_from_slice(&bytes[28..48]);
+        handshake.peer_id.copy_from_slice(&bytes[48..68]);
+        Ok(handshake)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake() -> Handshake {
+        let mut handshake = Handshake::new([1; 20], *b"-RT0001-abcdefghijkl");
+        handshake.reserved[5] = 0x10;
+        handshake
+    }
+
+    #[test]
+    fn should_encode_and_decode_handshakes() {
+        let handshake = handshake();
+        let bytes = handshake.encode();
+
+        assert_eq!(bytes.len(), HANDSHAKE_LEN);
+        assert_eq!(&bytes[..20], b"\x13BitTorrent protocol");
+        assert_eq!(&bytes[48..], b"-RT0001-abcdefghijkl");
+        let decoded = Handshake::decode(&bytes).unwrap();
+        assert_eq!(decoded, handshake);
+        assert!(decoded.supports_extension_protocol());
+        assert!(!Handshake::new([1; 20], [2; 20]).supports_extension_protocol());
+    }
+
+    #[test]
+    fn should_reject_malformed_handshakes() {
+        let bytes = handshake().encode();
+
+        let err = Handshake::decode(&bytes[..40]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "handshake of 40 bytes instead of 68");
+        assert!(Handshake::decode(&[]).is_err());
+
+        let mut wrong_pstrlen = bytes.clone();
+        wrong_pstrlen[0] = 18;
+        assert_eq!(
+            Handshake::decode(&wrong_pstrlen).unwrap_err().to_string(),
+            "handshake for an unknown protocol (pstrlen 18)"
+        );
+        let mut wrong_protocol = bytes;
+        wrong_protocol[1] = b'b';
+        assert!(Handshake::decode(&wrong_protocol).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use super::bitfield::Bitfield;
+use super::message::Message;
+
+/// Our interest in a peer, kept in sync with the pieces it has.
+/// We are interested as long as the peer has a piece we don't have yet,
+/// and may only request blocks once it unchoked us too.
+#[derive(Debug, Clone)]
+pub struct InterestManager {
+    peer_pieces: Bitfield,
+    interested: bool,
+    /// peers start out choking us
+    peer_choking: bool,
+}
+
+impl InterestManager {
+    pub fn new(num_pieces: usize) -> Self {
+        Self {
+            peer_pieces: Bitfield::new(num_pieces),
+            interested: false,
+            peer_choking: true,
+        }
+    }
+
+    pub fn is_interested(&self) -> bool {
+        self.interested
+    }
+
+    /// Whether we can send `request` messages to the peer
+    pub fn can_request(&self) -> bool {
+        self.interested && !self.peer_choking
+    }
+
+    pub fn peer_pieces(&self) -> &Bitfield {
+        &self.peer_pieces
+    }
+
+    /// The peer sent its `bitfield`. Returns the message to send
+    /// if our interest changed.
+    pub fn on_bitfield(&mut self, peer_pieces: Bitfield, ours: &Bitfield) -> Option<Message> {
+        self.peer_pieces = peer_pieces;
+        self.update(ours)
+    }
+
+    /// The peer sent a `have` for the given piece. Returns the message
+    /// to send if our interest changed.
+    pub fn on_have(&mut self, index: usize, ours: &Bitfield) -> Option<Message> {
+        self.peer_pieces.set(index);
+        self.update(ours)
+    }
+
+    /// We completed a piece, which may leave the peer with nothing we want.
+    /// Returns the message to send if our interest changed.
+    pub fn on_piece_completed(&mut self, ours: &Bitfield) -> Option<Message> {
+        self.update(ours)
+    }
+
+    /// Track `choke` and `unchoke` messages from the peer
+    pub fn on_message(&mut self, message: &Message) {
+        match message {
+            Message::Choke => self.peer_choking = true,
+            Message::Unchoke => self.peer_choking = false,
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, ours: &Bitfield) -> Option<Message> {
+        let interested = self.peer_pieces.iter_set().any(|index| !ours.has(index));
+        if interested == self.interested {
+            return None;
+        }
+        self.interested = interested;
+        Some(if interested {
+            Message::Interested
+        } else {
+            Message::NotInterested
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitfield(num_pieces: usize, pieces: &[usize]) -> Bitfield {
+        let mut bitfield = Bitfield::new(num_pieces);
+        for &index in pieces {
+            bitfield.set(index);
+        }
+        bitfield
+    }
+
+    #[test]
+    fn should_become_interested_when_the_peer_gets_a_needed_piece() {
+        let ours = bitfield(4, &[0, 1]);
+        let mut interest = InterestManager::new(4);
+
+        // the peer only has pieces we already have
+        assert_eq!(interest.on_bitfield(bitfield(4, &[0, 1]), &ours), None);
+        assert!(!interest.is_interested());
+        assert_eq!(interest.on_have(1, &ours), None);
+
+        assert_eq!(interest.on_have(3, &ours), Some(Message::Interested));
+        assert!(interest.is_interested());
+        assert_eq!(interest.on_have(2, &ours), None);
+    }
+
+    #[test]
+    fn should_lose_interest_once_we_have_every_piece_of_the_peer() {
+        let mut ours = bitfield(4, &[0]);
+        let mut interest = InterestManager::new(4);
+        assert_eq!(
+            interest.on_bitfield(bitfield(4, &[0, 2]), &ours),
+            Some(Message::Interested)
+        );
+
+        interest.on_message(&Message::Unchoke);
+        assert!(interest.can_request());
+        interest.on_message(&Message::Choke);
+        assert!(!interest.can_request());
+
+        ours.set(2);
+        assert_eq!(
+            interest.on_piece_completed(&ours),
+            Some(Message::NotInterested)
+        );
+        assert!(!interest.can_request());
+    }
+}
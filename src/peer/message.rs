@@ -0,0 +1,305 @@
+use std::io::{self, ErrorKind};
+
+/// Messages of the peer wire protocol.
+/// See the [Peer wire protocol spec here](https://wiki.theory.org/BitTorrentSpecification#Peer_wire_protocol_.28TCP.29) (unofficial)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    KeepAlive,
+    Choke,
+    Unchoke,
+    Interested,
+    NotInterested,
+    Have(u32),
+    Bitfield(Vec<u8>),
+    Request {
+        index: u32,
+        begin: u32,
+        length: u32,
+    },
+    Piece {
+        index: u32,
+        begin: u32,
+        block: Vec<u8>,
+    },
+    Cancel {
+        index: u32,
+        begin: u32,
+        length: u32,
+    },
+    /// Listening port of the peer's DHT node (BEP 5)
+    Port(u16),
+    /// Extension protocol message (BEP 10). Id 0 is the extended handshake.
+    Extended {
+        id: u8,
+        payload: Vec<u8>,
+    },
+}
+
+impl Message {
+    /// Decode the payload of a single message, without its length prefix
+    pub fn decode(payload: &[u8]) -> io::Result<Self> {
+        let Some((&id, body)) = payload.split_first() else {
+            return Ok(Message::KeepAlive);
+        };
+
+        let message = match (id, body.len()) {
+            (0, 0) => Message::Choke,
+            (1, 0) => Message::Unchoke,
+            (2, 0) => Message::Interested,
+            (3, 0) => Message::NotInterested,
+            (4, 4) => Message::Have(read_u32(body, 0)),
+            (5, _) => Message::Bitfield(body.to_vec()),
+            (6, 12) => Message::Request {
+                index: read_u32(body, 0),
+                begin: read_u32(body, 4),
+                length: read_u32(body, 8),
+            },
+            (7, len) if len >= 8 => Message::Piece {
+                index: read_u32(body, 0),
+                begin: read_u32(body, 4),
+                block: body[8..].to_vec(),
+            },
+            (8, 12) => Message::Cancel {
+                index: read_u32(body, 0),
+                begin: read_u32(body, 4),
+                length: read_u32(body, 8),
+            },
+            (9, 2) => Message::Port(u16::from_be_bytes([body[0], body[1]])),
+            (20, len) if len >= 1 => Message::Extended {
+                id: body[0],
+                payload: body[1..].to_vec(),
+            },
+            (id, len) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid peer message with id {} and length {}", id, len),
+                ))
+            }
+        };
+
+        Ok(message)
+    }
+
+    /// Encode the message, including its length prefix
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            Message::KeepAlive => {}
+            Message::Choke => payload.push(0),
+            Message::Unchoke => payload.push(1),
+            Message::Interested => payload.push(2),
+            Message::NotInterested => payload.push(3),
+            Message::Have(index) => {
+                payload.push(4);
+                payload.extend(index.to_be_bytes());
+            }
+            Message::Bitfield(bitfield) => {
+                payload.push(5);
+                payload.extend(bitfield);
+            }
+            Message::Request {
+                index,
+                begin,
+                length,
+            } => {
+                payload.push(6);
+                payload.extend(index.to_be_bytes());
+                payload.extend(begin.to_be_bytes());
+                payload.extend(length.to_be_bytes());
+            }
+            Message::Piece {
+                index,
+                begin,
+                block,
+            } => {
+                payload.push(7);
+                payload.extend(index.to_be_bytes());
+                payload.extend(begin.to_be_bytes());
+                payload.extend(block);
+            }
+            Message::Cancel {
+                index,
+                begin,
+                length,
+            } => {
+                payload.push(8);
+                payload.extend(index.to_be_bytes());
+                payload.extend(begin.to_be_bytes());
+                payload.extend(length.to_be_bytes());
+            }
+            Message::Port(port) => {
+                payload.push(9);
+                payload.extend(port.to_be_bytes());
+            }
+            Message::Extended { id, payload: body } => {
+                payload.push(20);
+                payload.push(*id);
+                payload.extend(body);
+            }
+        }
+
+        let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(payload);
+        bytes
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// Length of the big endian prefix every message starts with
+const LENGTH_PREFIX_LEN: usize = 4;
+
+/// Longest message accepted from peers, prefix excluded. It fits a 16 KiB
+/// block with its header as well as the bitfield of a million pieces.
+pub const MAX_MESSAGE_LEN: usize = 128 * 1024;
+
+/// Accumulates bytes read from a peer connection and splits them into messages.
+///
+/// TCP doesn't preserve message boundaries, so a single read may hold
+/// half a message or several of them. Incomplete messages are kept
+/// until the rest of their bytes come in.
+#[derive(Debug, Default)]
+pub struct MessageBuffer {
+    buffer: Vec<u8>,
+}
+
+impl MessageBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes freshly read from the connection
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Take the next complete message out of the buffer,
+    /// or `None` if more bytes are needed first.
+    pub fn next_message(&mut self) -> io::Result<Option<Message>> {
+        if self.buffer.len() < LENGTH_PREFIX_LEN {
+            return Ok(None);
+        }
+
+        let message_len = read_u32(&self.buffer, 0) as usize;
+        // rejected right away rather than buffering up to 4 GiB for it
+        if message_len > MAX_MESSAGE_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "peer message of {} bytes exceeds the maximum of {} bytes",
+                    message_len, MAX_MESSAGE_LEN
+                ),
+            ));
+        }
+        let end = LENGTH_PREFIX_LEN + message_len;
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+
+        let message = Message::decode(&self.buffer[LENGTH_PREFIX_LEN..end]);
+        self.buffer.drain(..end);
+        message.map(Some)
+    }
+
+    /// Number of buffered bytes not yet returned as a message
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn sample_messages() -> Vec<Message> {
+        vec![
+            Message::KeepAlive,
+            Message::Unchoke,
+            Message::Have(42),
+            Message::Bitfield(vec![0b1010_0000, 0xff]),
+            Message::Request {
+                index: 1,
+                begin: 16384,
+                length: 16384,
+            },
+            Message::Piece {
+                index: 1,
+                begin: 0,
+                block: (0..=255).collect(),
+            },
+            Message::Port(6881),
+        ]
+    }
+
+    fn drain(buffer: &mut MessageBuffer) -> Vec<Message> {
+        let mut messages = Vec::new();
+        while let Some(message) = buffer.next_message().unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[test]
+    fn should_encode_and_decode_messages() {
+        for message in sample_messages() {
+            let bytes = message.encode();
+            assert_eq!(Message::decode(&bytes[4..]).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn should_split_messages_across_arbitrary_reads() {
+        let messages = sample_messages();
+        let bytes: Vec<u8> = messages.iter().flat_map(Message::encode).collect();
+        let mut buffer = MessageBuffer::new();
+        let mut received = Vec::new();
+
+        // the first half trickles in one byte at a time...
+        let (head, tail) = bytes.split_at(bytes.len() / 2);
+        for byte in head {
+            buffer.extend(&[*byte]);
+            received.extend(drain(&mut buffer));
+        }
+        assert!(received.len() < messages.len());
+        assert!(!buffer.is_empty());
+
+        // ...and the rest comes in a single big chunk
+        buffer.extend(tail);
+        received.extend(drain(&mut buffer));
+
+        assert_eq!(received, messages);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn should_reject_messages_with_an_invalid_length() {
+        let mut buffer = MessageBuffer::new();
+        buffer.extend(&[0, 0, 0, 2, 4, 1]);
+        assert!(buffer.next_message().is_err());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn should_reject_messages_over_the_maximum_length() {
+        let mut buffer = MessageBuffer::new();
+        buffer.extend(&[0xff, 0xff, 0xff, 0xff, 7]);
+        let err = buffer.next_message().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let block = Message::Piece {
+            index: 0,
+            begin: 0,
+            block: vec![0; 16 * 1024],
+        };
+        let mut buffer = MessageBuffer::new();
+        buffer.extend(&block.encode());
+        assert_eq!(buffer.next_message().unwrap(), Some(block));
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hasher};
+
+/// Client software a peer runs, as announced by its peer id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub name: &'static str,
+    pub version: String,
+}
+
+impl Display for ClientInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// Azureus-style client codes: `-XX1234-`
+const AZUREUS_CLIENTS: &[(&[u8; 2], &str)] = &[
+    (b"AZ", "Vuze"),
+    (b"BC", "BitComet"),
+    (b"BT", "BitTorrent"),
+    (b"DE", "Deluge"),
+    (b"KT", "KTorrent"),
+    (b"LT", "libtorrent (Rasterbar)"),
+    (b"lt", "libTorrent (Rakshasa)"),
+    (b"qB", "qBittorrent"),
+    (b"TR", "Transmission"),
+    (b"UM", "µTorrent for Mac"),
+    (b"UT", "µTorrent"),
+    (b"WW", "WebTorrent"),
+];
+
+/// Shadow-style client codes: `X12345---`
+const SHADOW_CLIENTS: &[(u8, &str)] = &[
+    (b'A', "ABC"),
+    (b'O', "Osprey Permaseed"),
+    (b'Q', "BTQueue"),
+    (b'R', "Tribler"),
+    (b'S', "Shadow's client"),
+    (b'T', "BitTornado"),
+    (b'U', "UPnP NAT Bit Torrent"),
+];
+
+/// Characters the random part of generated peer ids is made of
+const PEER_ID_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Generate a 20-byte peer id starting with the given client prefix,
+/// usually Azureus-style such as `-RT0001-`, the rest being random
+/// alphanumeric characters.
+///
+/// # Panics
+///
+/// If the prefix is longer than 20 bytes.
+pub fn generate_peer_id(client_prefix: &str) -> [u8; 20] {
+    let prefix = client_prefix.as_bytes();
+    assert!(
+        prefix.len() <= 20,
+        "peer id prefix '{}' is longer than 20 bytes",
+        client_prefix
+    );
+
+    let mut peer_id = [0; 20];
+    peer_id[..prefix.len()].copy_from_slice(prefix);
+    // The std hasher is randomly seeded, which is plenty to
+    // tell apart the sessions of the same client.
+    let mut hasher = RandomState::new().build_hasher();
+    for (index, byte) in peer_id.iter_mut().enumerate().skip(prefix.len()) {
+        hasher.write_usize(index);
+        *byte = PEER_ID_CHARS[hasher.finish() as usize % PEER_ID_CHARS.len()];
+    }
+    peer_id
+}
+
+/// Identify the client software from the well-known peer id conventions
+/// (Azureus-style such as `-AZ2060-`, Shadow-style such as `S58B-----`
+/// and Mainline-style such as `M4-3-6--`).
+/// See the [peer id spec here](https://wiki.theory.org/BitTorrentSpecification#peer_id) (unofficial)
+pub fn identify_client(peer_id: &[u8]) -> Option<ClientInfo> {
+    identify_azureus(peer_id)
+        .or_else(|| identify_mainline(peer_id))
+        .or_else(|| identify_shadow(peer_id))
+}
+
+fn identify_azureus(peer_id: &[u8]) -> Option<ClientInfo> {
+    let [b'-', c1, c2, version @ .., b'-'] = peer_id.get(..8)? else {
+        return None;
+    };
+    let (_, name) = AZUREUS_CLIENTS
+        .iter()
+        .find(|(code, _)| **code == [*c1, *c2])?;
+    if !version.iter().all(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+    let version: Vec<String> = version
+        .iter()
+        .map(|&byte| (byte as char).to_string())
+        .collect();
+    Some(ClientInfo {
+        name,
+        version: version.join("."),
+    })
+}
+
+fn identify_mainline(peer_id: &[u8]) -> Option<ClientInfo> {
+    let [b'M', rest @ ..] = peer_id.get(..8)? else {
+        return None;
+    };
+    // M followed by the version digits separated by dashes, e.g. M4-3-6--
+    let version: Vec<&[u8]> = rest
+        .split(|&byte| byte == b'-')
+        .filter(|part| !part.is_empty())
+        .collect();
+    if version.is_empty()
+        || !version
+            .iter()
+            .all(|part| part.iter().all(u8::is_ascii_digit))
+    {
+        return None;
+    }
+    let version: Vec<String> = version
+        .iter()
+        .map(|part| String::from_utf8_lossy(part).into_owned())
+        .collect();
+    Some(ClientInfo {
+        name: "Mainline",
+        version: version.join("."),
+    })
+}
+
+fn identify_shadow(peer_id: &[u8]) -> Option<ClientInfo> {
+    let (code, rest) = peer_id.get(..7)?.split_first()?;
+    let (_, name) = SHADOW_CLIENTS.iter().find(|(c, _)| c == code)?;
+    // up to five version digits, padded with `-`
+    let version_len = rest.iter().position(|&byte| byte == b'-')?;
+    let version: Option<Vec<String>> = rest[..version_len]
+        .iter()
+        .map(|&byte| shadow_digit(byte).map(|digit| digit.to_string()))
+        .collect();
+    let version = version?;
+    if version.is_empty() {
+        return None;
+    }
+    Some(ClientInfo {
+        name,
+        version: version.join("."),
+    })
+}
+
+/// Shadow-style version digits: 0-9, then A-Z for 10-35, a-z for 36-61 and `.` for 62
+fn shadow_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'A'..=b'Z' => Some(byte - b'A' + 10),
+        b'a'..=b'z' => Some(byte - b'a' + 36),
+        b'.' => Some(62),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn identify(peer_id: &str) -> Option<String> {
+        identify_client(peer_id.as_bytes()).map(|client| client.to_string())
+    }
+
+    #[test]
+    fn should_generate_peer_ids_with_the_client_prefix() {
+        let peer_id = generate_peer_id("-RT0001-");
+
+        assert_eq!(peer_id.len(), 20);
+        assert!(peer_id.starts_with(b"-RT0001-"));
+        assert!(peer_id[8..].iter().all(u8::is_ascii_alphanumeric));
+        assert_ne!(generate_peer_id("-RT0001-"), peer_id);
+    }
+
+    #[test]
+    fn should_identify_azureus_style_peer_ids() {
+        assert_eq!(
+            identify("-AZ2060-abcdefghijkl").as_deref(),
+            Some("Vuze 2.0.6.0")
+        );
+        assert_eq!(
+            identify("-qB4250-abcdefghijkl").as_deref(),
+            Some("qBittorrent 4.2.5.0")
+        );
+        assert_eq!(
+            identify("-TR2940-abcdefghijkl").as_deref(),
+            Some("Transmission 2.9.4.0")
+        );
+    }
+
+    #[test]
+    fn should_identify_shadow_and_mainline_style_peer_ids() {
+        assert_eq!(
+            identify("S58B-----abcdefghijk").as_deref(),
+            Some("Shadow's client 5.8.11")
+        );
+        assert_eq!(
+            identify("T03I--00abcdefghijkl").as_deref(),
+            Some("BitTornado 0.3.18")
+        );
+        assert_eq!(
+            identify("M4-3-6--abcdefghijkl").as_deref(),
+            Some("Mainline 4.3.6")
+        );
+        assert_eq!(
+            identify("M4-10-6-abcdefghijkl").as_deref(),
+            Some("Mainline 4.10.6")
+        );
+    }
+
+    #[test]
+    fn should_not_identify_unknown_peer_ids() {
+        assert_eq!(identify("-ZZ1234-abcdefghijkl"), None);
+        assert_eq!(identify("rustorrent-client-dev"), None);
+        assert_eq!(identify("-AZ"), None);
+    }
+}
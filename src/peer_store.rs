@@ -0,0 +1,195 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+
+use crate::parser::announce_info::Peer;
+
+/// How long to wait before retrying a peer after its first failure.
+/// Doubled with every consecutive failure, up to [`MAX_BACKOFF`].
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(30);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Result of an attempt to connect to a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionOutcome {
+    Connected,
+    Refused,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PeerHealth {
+    connected: bool,
+    /// consecutive failed connection attempts
+    failures: u32,
+    last_outcome: Option<ConnectionOutcome>,
+    /// the peer shouldn't be tried again before then
+    retry_at: Option<Instant>,
+}
+
+/// Peers discovered for a torrent (from trackers, DHT...), along with
+/// the outcome of our connections to them. Peers failing to connect are
+/// backed off exponentially so unreachable ones don't waste connection slots.
+#[derive(Debug, Default)]
+pub struct PeerStore {
+    peers: IndexMap<SocketAddr, PeerHealth>,
+}
+
+impl PeerStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a peer, returns `false` if it was already known
+    pub fn add(&mut self, addr: SocketAddr) -> bool {
+        if self.peers.contains_key(&addr) {
+            return false;
+        }
+        self.peers.insert(addr, PeerHealth::default());
+        true
+    }
+
+    /// Add the peers returned by a tracker, skipping the ones whose address
+    /// isn't an IP (trackers may send hostnames). Returns how many were new.
+    pub fn add_peers(&mut self, peers: &[Peer]) -> usize {
+        peers
+            .iter()
+            .filter_map(|peer| {
+                let port = u16::try_from(peer.port).ok()?;
+                Some(SocketAddr::new(peer.ip.parse().ok()?, port))
+            })
+            .filter(|addr| self.add(*addr))
+            .count()
+    }
+
+    pub fn mark_connected(&mut self, addr: SocketAddr) {
+        let health = self.peers.entry(addr).or_default();
+        health.connected = true;
+        health.failures = 0;
+        health.last_outcome = Some(ConnectionOutcome::Connected);
+        health.retry_at = None;
+    }
+
+    /// Record a failed connection attempt (`outcome` being why it failed)
+    /// and back the peer off until `now` + its backoff.
+    pub fn mark_failed(&mut self, addr: SocketAddr, outcome: ConnectionOutcome, now: Instant) {
+        let health = self.peers.entry(addr).or_default();
+        health.connected = false;
+        health.failures += 1;
+        health.last_outcome = Some(outcome);
+        health.retry_at = Some(now + backoff(health.failures));
+    }
+
+    /// The connection to the peer was closed, it can be tried again right away
+    pub fn mark_disconnected(&mut self, addr: SocketAddr) {
+        if let Some(health) = self.peers.get_mut(&addr) {
+            health.connected = false;
+        }
+    }
+
+    /// Outcome of the last connection attempt to the peer, if any
+    pub fn last_outcome(&self, addr: SocketAddr) -> Option<ConnectionOutcome> {
+        self.peers.get(&addr).and_then(|health| health.last_outcome)
+    }
+
+    /// Pick up to `max` peers to connect to: peers we're not connected to
+    /// and aren't backed off, the ones that failed the least coming first.
+    pub fn connection_batch(&self, max: usize, now: Instant) -> Vec<SocketAddr> {
+        let mut candidates: Vec<(&SocketAddr, &PeerHealth)> = self
+            .peers
+            .iter()
+            .filter(|(_, health)| {
+                !health.connected && health.retry_at.is_none_or(|retry_at| retry_at <= now)
+            })
+            .collect();
+        // stable, so peers that failed as often keep the discovery order
+        candidates.sort_by_key(|(_, health)| health.failures);
+        candidates
+            .into_iter()
+            .take(max)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+fn backoff(failures: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(last_byte: u8) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, last_byte], 6881))
+    }
+
+    #[test]
+    fn should_back_off_peers_failing_to_connect() {
+        let now = Instant::now();
+        let mut store = PeerStore::new();
+        for last_byte in 1..=3 {
+            store.add(addr(last_byte));
+        }
+
+        store.mark_failed(addr(1), ConnectionOutcome::Refused, now);
+        store.mark_failed(addr(1), ConnectionOutcome::TimedOut, now);
+        store.mark_failed(addr(1), ConnectionOutcome::TimedOut, now);
+        store.mark_connected(addr(2));
+        assert_eq!(
+            store.last_outcome(addr(1)),
+            Some(ConnectionOutcome::TimedOut)
+        );
+
+        // backed off for 30s * 2^2 after the third failure
+        assert_eq!(store.connection_batch(10, now), vec![addr(3)]);
+        assert_eq!(
+            store.connection_batch(10, now + Duration::from_secs(119)),
+            vec![addr(3)]
+        );
+
+        // once its backoff elapsed, the peer comes after the healthy ones
+        store.mark_disconnected(addr(2));
+        assert_eq!(
+            store.connection_batch(10, now + Duration::from_secs(120)),
+            vec![addr(2), addr(3), addr(1)]
+        );
+        assert_eq!(
+            store.connection_batch(2, now + Duration::from_secs(120)),
+            vec![addr(2), addr(3)]
+        );
+    }
+
+    #[test]
+    fn should_cap_the_backoff() {
+        assert_eq!(backoff(1), INITIAL_BACKOFF);
+        assert_eq!(backoff(2), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff(100), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn should_add_tracker_peers_once() {
+        let peer = |ip: &str| Peer {
+            peer_id: String::new(),
+            ip: ip.to_string(),
+            port: 6881,
+        };
+        let mut store = PeerStore::new();
+
+        let peers = [peer("10.0.0.1"), peer("tracker.example"), peer("::1")];
+        assert_eq!(store.add_peers(&peers), 2);
+        assert_eq!(store.add_peers(&peers), 0);
+        assert_eq!(store.len(), 2);
+    }
+}
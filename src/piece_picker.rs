@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+
+use crate::parser::meta_info::{FileMode, Info};
+use crate::peer::bitfield::Bitfield;
+use crate::peer::message::Message;
+
+/// How much we want the pieces of a file, for selective downloading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum FilePriority {
+    /// not downloaded, unless a piece is shared with a wanted file
+    Skip,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Keeps track of which pieces each connected peer has,
+/// and how many peers have each piece (its availability).
+#[derive(Debug)]
+pub struct PiecePicker {
+    num_pieces: usize,
+    /// Number of connected peers having each piece
+    availability: Vec<u32>,
+    peers: HashMap<SocketAddr, Bitfield>,
+    /// Pieces of each file of the torrent, `None` for empty files
+    file_pieces: Vec<Option<RangeInclusive<usize>>>,
+    file_priorities: Vec<FilePriority>,
+    /// Highest priority of the files each piece belongs to
+    piece_priorities: Vec<FilePriority>,
+}
+
+impl PiecePicker {
+    pub fn new(num_pieces: usize) -> Self {
+        Self {
+            num_pieces,
+            availability: vec![0; num_pieces],
+            peers: HashMap::new(),
+            file_pieces: Vec::new(),
+            file_priorities: Vec::new(),
+            piece_priorities: vec![FilePriority::Normal; num_pieces],
+        }
+    }
+
+    /// Picker knowing which pieces belong to which file of the torrent,
+    /// so files can be given a priority with [`PiecePicker::set_file_priority`]
+    pub fn for_info(info: &Info) -> Self {
+        let num_files = match &info.file_info {
+            FileMode::Single(_) => 1,
+            FileMode::Multi(multi) => multi.files.len(),
+        };
+        Self {
+            file_pieces: (0..num_files)
+                .map(|index| info.pieces_for_file(index))
+                .collect(),
+            file_priorities: vec![FilePriority::Normal; num_files],
+            ..Self::new(info.num_pieces())
+        }
+    }
+
+    /// Set the priority of the file at `file_index`. Returns `false`
+    /// if there is no such file.
+    pub fn set_file_priority(&mut self, file_index: usize, priority: FilePriority) -> bool {
+        let Some(file_priority) = self.file_priorities.get_mut(file_index) else {
+            return false;
+        };
+        *file_priority = priority;
+
+        self.piece_priorities = vec![FilePriority::Skip; self.num_pieces];
+        for (pieces, &priority) in self.file_pieces.iter().zip(&self.file_priorities) {
+            for index in pieces.clone().into_iter().flatten() {
+                if let Some(piece_priority) = self.piece_priorities.get_mut(index) {
+                    *piece_priority = (*piece_priority).max(priority);
+                }
+            }
+        }
+        true
+    }
+
+    /// Pick the next piece to download from `peer`, among the ones it has
+    /// and we don't: pieces of the highest priority files first, then
+    /// the rarest ones. Pieces of skipped files are never picked.
+    pub fn next_piece(&self, peer: &SocketAddr, have: &Bitfield) -> Option<usize> {
+        self.peers
+            .get(peer)?
+            .iter_set()
+            .filter(|&index| !have.has(index) && self.piece_priorities[index] != FilePriority::Skip)
+            .min_by_key(|&index| {
+                (
+                    std::cmp::Reverse(self.piece_priorities[index]),
+                    self.availability[index],
+                )
+            })
+    }
+
+    /// Update the pieces of a peer from a message it sent us.
+    /// Only `bitfield` and `have` messages are relevant, others are ignored.
+    pub fn handle_message(&mut self, peer: SocketAddr, message: &Message) -> io::Result<()> {
+        match message {
+            Message::Bitfield(bytes) => {
+                let Some(bitfield) = Bitfield::from_bytes(bytes, self.num_pieces) else {
+                    return Err(invalid_data(format!(
+                        "bitfield of {} bytes doesn't match {} pieces",
+                        bytes.len(),
+                        self.num_pieces
+                    )));
+                };
+                self.remove_peer(peer);
+                for index in bitfield.iter_set() {
+                    self.availability[index] += 1;
+                }
+                self.peers.insert(peer, bitfield);
+            }
+            Message::Have(index) => {
+                let index = *index as usize;
+                if index >= self.num_pieces {
+                    return Err(invalid_data(format!(
+                        "have for piece {} out of {} pieces",
+                        index, self.num_pieces
+                    )));
+                }
+                // peers may skip the bitfield message when they have no piece yet
+                let bitfield = self
+                    .peers
+                    .entry(peer)
+                    .or_insert_with(|| Bitfield::new(self.num_pieces));
+                if bitfield.set(index) {
+                    self.availability[index] += 1;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Forget about a disconnected peer and its pieces
+    pub fn remove_peer(&mut self, peer: SocketAddr) {
+        if let Some(bitfield) = self.peers.remove(&peer) {
+            for index in bitfield.iter_set() {
+                self.availability[index] -= 1;
+            }
+        }
+    }
+
+    /// Number of connected peers having the given piece
+    pub fn availability(&self, index: usize) -> u32 {
+        self.availability.get(index).copied().unwrap_or(0)
+    }
+
+    pub fn peer_bitfield(&self, peer: &SocketAddr) -> Option<&Bitfield> {
+        self.peers.get(peer)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::byte_string::ByteString;
+    use crate::parser::meta_info::{MultiFile, MultiFileItem, PIECE_HASH_LEN};
+
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn should_update_availability_on_have_messages() {
+        let mut picker = PiecePicker::new(4);
+        let (alice, bob) = (peer(6881), peer(6882));
+
+        picker
+            .handle_message(alice, &Message::Bitfield(vec![0b1000_0000]))
+            .unwrap();
+        for (from, index) in [(alice, 2), (bob, 2), (bob, 3), (bob, 3)] {
+            picker.handle_message(from, &Message::Have(index)).unwrap();
+        }
+
+        let availability: Vec<u32> = (0..4).map(|index| picker.availability(index)).collect();
+        assert_eq!(availability, vec![1, 0, 2, 1]);
+        assert!(picker.peer_bitfield(&bob).unwrap().has(3));
+
+        picker.remove_peer(bob);
+        let availability: Vec<u32> = (0..4).map(|index| picker.availability(index)).collect();
+        assert_eq!(availability, vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn should_pick_the_rarest_piece_first() {
+        let mut picker = PiecePicker::new(4);
+        let (alice, bob) = (peer(6881), peer(6882));
+        picker
+            .handle_message(alice, &Message::Bitfield(vec![0b1111_0000]))
+            .unwrap();
+        picker
+            .handle_message(bob, &Message::Bitfield(vec![0b1010_0000]))
+            .unwrap();
+
+        let mut have = Bitfield::new(4);
+        assert_eq!(picker.next_piece(&alice, &have), Some(1));
+        have.set(1);
+        assert_eq!(picker.next_piece(&alice, &have), Some(3));
+        have.set(3);
+        assert_eq!(picker.next_piece(&alice, &have), Some(0));
+        assert_eq!(picker.next_piece(&peer(6883), &have), None);
+    }
+
+    #[test]
+    fn should_never_pick_the_pieces_only_held_by_skipped_files() {
+        let file = |length: u64| MultiFileItem {
+            length,
+            md5sum: None,
+            path: vec![format!("{}.bin", length)],
+        };
+        // pieces of 10 bytes: the first file has pieces 0 and 1,
+        // the second one shares piece 1 and the last one has pieces 2 to 4
+        let file_info = FileMode::Multi(MultiFile {
+            name: String::from("files"),
+            files: vec![file(15), file(5), file(25)],
+        });
+        let pieces = ByteString::from_vec(vec![b'a'; 5 * PIECE_HASH_LEN]);
+        let info = Info::new(10, pieces, file_info, false).unwrap();
+        let mut picker = PiecePicker::for_info(&info);
+        let seeder = peer(6881);
+        picker
+            .handle_message(seeder, &Message::Bitfield(vec![0b1111_1000]))
+            .unwrap();
+
+        assert!(picker.set_file_priority(0, FilePriority::Skip));
+        assert!(picker.set_file_priority(2, FilePriority::Skip));
+        assert!(!picker.set_file_priority(3, FilePriority::Skip));
+
+        let mut have = Bitfield::new(5);
+        let mut picked = Vec::new();
+        while let Some(index) = picker.next_piece(&seeder, &have) {
+            have.set(index);
+            picked.push(index);
+        }
+        // piece 1 is still needed for the second file
+        assert_eq!(picked, vec![1]);
+
+        assert!(picker.set_file_priority(2, FilePriority::High));
+        assert!(picker.set_file_priority(0, FilePriority::Normal));
+        assert_eq!(picker.next_piece(&seeder, &have), Some(2));
+    }
+
+    #[test]
+    fn should_reject_have_messages_out_of_range() {
+        let mut picker = PiecePicker::new(4);
+        assert!(picker
+            .handle_message(peer(6881), &Message::Have(4))
+            .is_err());
+        assert_eq!(picker.availability(3), 0);
+    }
+}
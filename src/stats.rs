@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Live statistics of a download session, e.g. to be displayed by a UI.
+///
+/// The download engine periodically reports its cumulative byte counters
+/// through [`SessionStats::update`], and transfer rates are computed over
+/// the most recent `window` so they follow changes in speed without jumping
+/// around with every single block received.
+#[derive(Debug, Clone)]
+pub struct SessionStats {
+    /// size of the torrent content, in bytes
+    pub total_length: u64,
+    pub peers_connected: usize,
+    pub pieces_complete: usize,
+    window: Duration,
+    /// (time, total downloaded, total uploaded) samples, oldest first
+    samples: VecDeque<(Instant, u64, u64)>,
+}
+
+impl SessionStats {
+    pub fn new(total_length: u64, window: Duration) -> Self {
+        Self {
+            total_length,
+            peers_connected: 0,
+            pieces_complete: 0,
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record the total bytes downloaded and uploaded so far, at the given time.
+    pub fn update(&mut self, now: Instant, downloaded: u64, uploaded: u64) {
+        self.samples.push_back((now, downloaded, uploaded));
+        // Keep the newest sample at or before the start of the window,
+        // so rates are computed over the whole window.
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Total bytes downloaded so far
+    pub fn downloaded(&self) -> u64 {
+        self.samples
+            .back()
+            .map_or(0, |&(_, downloaded, _)| downloaded)
+    }
+
+    /// Total bytes uploaded so far
+    pub fn uploaded(&self) -> u64 {
+        self.samples.back().map_or(0, |&(_, _, uploaded)| uploaded)
+    }
+
+    /// Download rate over the window, in bytes per second
+    pub fn download_rate(&self) -> f64 {
+        self.rate(|&(_, downloaded, _)| downloaded)
+    }
+
+    /// Upload rate over the window, in bytes per second
+    pub fn upload_rate(&self) -> f64 {
+        self.rate(|&(_, _, uploaded)| uploaded)
+    }
+
+    /// Estimated time left to complete the download at the current rate,
+    /// `None` while nothing is being downloaded.
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total_length.saturating_sub(self.downloaded());
+        if remaining == 0 {
+            return Some(Duration::ZERO);
+        }
+        let rate = self.download_rate();
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    fn rate(&self, bytes: impl Fn(&(Instant, u64, u64)) -> u64) -> f64 {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return 0.0;
+        };
+        let elapsed = last.0.duration_since(first.0).as_secs_f64();
+        if elapsed == 0.0 {
+            return 0.0;
+        }
+        bytes(last).saturating_sub(bytes(first)) as f64 / elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_compute_rates_and_eta_over_the_window() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut stats = SessionStats::new(100_000, Duration::from_secs(5));
+        assert_eq!(stats.download_rate(), 0.0);
+        assert_eq!(stats.eta(), None);
+
+        // 1000 B/s down and 100 B/s up for 10 seconds
+        for secs in 0..=10 {
+            stats.update(at(secs), secs * 1000, secs * 100);
+        }
+        assert_eq!(stats.download_rate(), 1000.0);
+        assert_eq!(stats.upload_rate(), 100.0);
+        assert_eq!(stats.eta(), Some(Duration::from_secs(90)));
+
+        // Speeding up to 3000 B/s: once the window only covers the new
+        // speed, the slower start doesn't weigh on the rate anymore.
+        for secs in 11..=15 {
+            stats.update(at(secs), 10_000 + (secs - 10) * 3000, secs * 100);
+        }
+        assert_eq!(stats.downloaded(), 25_000);
+        assert_eq!(stats.download_rate(), 3000.0);
+        assert_eq!(stats.eta(), Some(Duration::from_secs(25)));
+
+        stats.update(at(16), 100_000, 1600);
+        assert_eq!(stats.eta(), Some(Duration::ZERO));
+    }
+}
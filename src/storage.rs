@@ -0,0 +1,506 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::hash;
+#[cfg(feature = "md5")]
+use crate::parser::meta_info::SingleFile;
+use crate::parser::meta_info::{sanitize_file_name, FileMode, Info};
+
+/// Where the pieces of a torrent are written to and read back from.
+/// Pieces are addressed by their index, the backend takes care of
+/// mapping them to the underlying bytes (memory, files on disk...).
+pub trait Storage {
+    /// Write a complete piece. `data` must be exactly as long as the piece.
+    fn write_piece(&mut self, index: usize, data: &[u8]) -> io::Result<()>;
+
+    /// Read a complete piece back
+    fn read_piece(&mut self, index: usize) -> io::Result<Vec<u8>>;
+}
+
+/// Verify every piece found in the given storage against its hash
+/// and report the indices of the ones that don't match.
+/// Pieces that can't be read (e.g. missing or truncated files) count as corrupt.
+pub fn corrupt_pieces<S: Storage>(info: &Info, storage: &mut S) -> Vec<usize> {
+    info.piece_hash_iter()
+        .enumerate()
+        .filter(|(index, expected_hash)| match storage.read_piece(*index) {
+            Ok(data) => hash::sha1(&data) != *expected_hash,
+            Err(_) => true,
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Check the downloaded file at `path` against the `md5sum` of the torrent,
+/// an extra integrity check some torrents provide on top of the piece hashes.
+/// Fails if the torrent has no `md5sum` or the file can't be read.
+#[cfg(feature = "md5")]
+pub fn verify_md5(file: &SingleFile, path: &Path) -> io::Result<bool> {
+    use md5::{Digest, Md5};
+
+    let Some(md5sum) = &file.md5sum else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("torrent file '{}' has no md5sum", file.name),
+        ));
+    };
+    let mut hasher = Md5::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    let digest = hash::hex(&hasher.finalize());
+    Ok(digest.eq_ignore_ascii_case(md5sum))
+}
+
+/// Keeps the whole torrent content in memory.
+/// Handy for tests and small torrents where touching the disk is overkill.
+#[derive(Debug)]
+pub struct MemoryStorage {
+    layout: PieceLayout,
+    data: Vec<u8>,
+}
+
+impl MemoryStorage {
+    pub fn new(info: &Info) -> Self {
+        let layout = PieceLayout::new(info);
+        let data = vec![0; layout.total_length as usize];
+        Self { layout, data }
+    }
+
+    /// The torrent content, with all files concatenated in order
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn write_piece(&mut self, index: usize, data: &[u8]) -> io::Result<()> {
+        let range = self.layout.checked_piece_range(index, data.len())?;
+        self.data[range.start as usize..range.end as usize].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn read_piece(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        let range = self.layout.piece_range(index)?;
+        Ok(self.data[range.start as usize..range.end as usize].to_vec())
+    }
+}
+
+/// Stores the torrent content in its files under a root directory,
+/// following the single/multi-file structure of the torrent.
+/// File and directory names are sanitized before touching the disk.
+#[derive(Debug)]
+pub struct FileStorage {
+    layout: PieceLayout,
+    files: Vec<StorageFile>,
+}
+
+#[derive(Debug)]
+struct StorageFile {
+    path: PathBuf,
+    /// Position of the first byte of this file within the whole torrent
+    offset: u64,
+    length: u64,
+}
+
+impl FileStorage {
+    pub fn new<P: AsRef<Path>>(root: P, info: &Info) -> Self {
+        let root = root.as_ref().join(info.safe_name());
+        let mut offset = 0;
+        let files = file_entries(info)
+            .into_iter()
+            .map(|(segments, length)| {
+                let path = segments.iter().fold(root.clone(), |path, segment| {
+                    path.join(sanitize_file_name(segment))
+                });
+                let file = StorageFile {
+                    path,
+                    offset,
+                    length,
+                };
+                offset += length;
+                file
+            })
+            .collect();
+
+        Self {
+            layout: PieceLayout::new(info),
+            files,
+        }
+    }
+
+    /// Create every file of the torrent at its full length up front, so pieces
+    /// can be written at their offset in any order. Files are only extended,
+    /// which leaves them sparse on filesystems supporting it (most Unix ones):
+    /// no zeros are written and no disk space is used until pieces come in.
+    /// Existing content, e.g. when resuming a download, is kept.
+    pub fn preallocate(&self) -> io::Result<()> {
+        for file in &self.files {
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let handle = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&file.path)?;
+            if handle.metadata()?.len() < file.length {
+                handle.set_len(file.length)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Path of every file of the torrent on disk, in torrent order
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+
+    /// The files overlapping the given byte range of the torrent,
+    /// along with the range to use within each file.
+    fn files_in(&self, range: Range<u64>) -> impl Iterator<Item = (&StorageFile, Range<u64>)> {
+        self.files.iter().filter_map(move |file| {
+            let start = range.start.max(file.offset);
+            let end = range.end.min(file.offset + file.length);
+            (start < end).then(|| (file, start - file.offset..end - file.offset))
+        })
+    }
+}
+
+impl Storage for FileStorage {
+    fn write_piece(&mut self, index: usize, data: &[u8]) -> io::Result<()> {
+        let range = self.layout.checked_piece_range(index, data.len())?;
+        let mut written = 0;
+        for (file, file_range) in self.files_in(range) {
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut handle = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&file.path)?;
+            handle.seek(SeekFrom::Start(file_range.start))?;
+            let len = (file_range.end - file_range.start) as usize;
+            handle.write_all(&data[written..written + len])?;
+            written += len;
+        }
+        Ok(())
+    }
+
+    fn read_piece(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        let range = self.layout.piece_range(index)?;
+        let mut data = vec![0; (range.end - range.start) as usize];
+        let mut read = 0;
+        for (file, file_range) in self.files_in(range) {
+            let mut handle = File::open(&file.path)?;
+            handle.seek(SeekFrom::Start(file_range.start))?;
+            let len = (file_range.end - file_range.start) as usize;
+            handle.read_exact(&mut data[read..read + len])?;
+            read += len;
+        }
+        Ok(data)
+    }
+}
+
+/// How the pieces of a torrent map onto its bytes
+#[derive(Debug, Clone)]
+struct PieceLayout {
+    piece_length: u64,
+    total_length: u64,
+    num_pieces: usize,
+}
+
+impl PieceLayout {
+    fn new(info: &Info) -> Self {
+        Self {
+            piece_length: info.piece_length,
+            total_length: file_entries(info).iter().map(|(_, length)| length).sum(),
+            num_pieces: info.num_pieces(),
+        }
+    }
+
+    /// Range of bytes of the whole torrent covered by the given piece.
+    /// The last piece is usually shorter than the others.
+    fn piece_range(&self, index: usize) -> io::Result<Range<u64>> {
+        let start = index as u64 * self.piece_length;
+        if index >= self.num_pieces || start >= self.total_length {
+            return Err(invalid_input(format!(
+                "piece index {} out of range ({} pieces)",
+                index, self.num_pieces
+            )));
+        }
+        let end = (start + self.piece_length).min(self.total_length);
+        Ok(start..end)
+    }
+
+    fn checked_piece_range(&self, index: usize, data_len: usize) -> io::Result<Range<u64>> {
+        let range = self.piece_range(index)?;
+        let expected = (range.end - range.start) as usize;
+        if data_len != expected {
+            return Err(invalid_input(format!(
+                "piece {} must be {} bytes long, got {}",
+                index, expected, data_len
+            )));
+        }
+        Ok(range)
+    }
+}
+
+/// Path segments (relative to the torrent root) and length of each file
+fn file_entries(info: &Info) -> Vec<(Vec<String>, u64)> {
+    match &info.file_info {
+        FileMode::Single(_) => vec![(vec![], info.total_length())],
+        FileMode::Multi(multi) => multi
+            .files
+            .iter()
+            .map(|file| (file.path.clone(), file.length))
+            .collect(),
+    }
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use indexmap::IndexMap;
+    use sha1::{Digest, Sha1};
+
+    use crate::parser::bencode::{Bencode, BencodeParser};
+    use crate::parser::byte_string::ByteString;
+    use crate::parser::meta_info::MetaInfo;
+
+    use super::*;
+
+    /// Build a torrent for the given content, split into the given files
+    fn synthetic_torrent(content: &[u8], piece_length: usize, files: &[(&str, usize)]) -> MetaInfo {
+        let pieces: Vec<u8> = content
+            .chunks(piece_length)
+            .flat_map(|chunk| Sha1::digest(chunk).to_vec())
+            .collect();
+
+        let mut info = IndexMap::from([
+            (
+                ByteString::new("name"),
+                Bencode::Text(ByteString::new("synthetic")),
+            ),
+            (
+                ByteString::new("piece length"),
+                Bencode::Number(piece_length as i64),
+            ),
+            (
+                ByteString::new("pieces"),
+                Bencode::Text(ByteString::from_vec(pieces)),
+            ),
+        ]);
+
+        if files.is_empty() {
+            info.insert(
+                ByteString::new("length"),
+                Bencode::Number(content.len() as i64),
+            );
+        } else {
+            let files = files
+                .iter()
+                .map(|(path, length)| {
+                    Bencode::Dict(IndexMap::from([
+                        (ByteString::new("length"), Bencode::Number(*length as i64)),
+                        (
+                            ByteString::new("path"),
+                            Bencode::List(
+                                path.split('/')
+                                    .map(|segment| Bencode::Text(ByteString::new(segment)))
+                                    .collect(),
+                            ),
+                        ),
+                    ]))
+                })
+                .collect();
+            info.insert(ByteString::new("files"), Bencode::List(files));
+        }
+
+        let torrent = Bencode::Dict(IndexMap::from([
+            (
+                ByteString::new("announce"),
+                Bencode::Text(ByteString::new("http://localhost/announce")),
+            ),
+            (ByteString::new("info"), Bencode::Dict(info)),
+        ]));
+
+        MetaInfo::from_bytes(&BencodeParser::encode(&torrent)).unwrap()
+    }
+
+    fn content(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn should_download_a_synthetic_torrent_into_memory() {
+        let content = content(100);
+        let meta_info = synthetic_torrent(&content, 32, &[]);
+        let info = &meta_info.info;
+        let mut storage = MemoryStorage::new(info);
+
+        // Pretend peers sent us the pieces in a random order
+        for index in [3, 0, 2, 1] {
+            let start = index * 32;
+            let piece = &content[start..(start + 32).min(content.len())];
+            let expected_hash = info.piece_hash_iter().nth(index).unwrap();
+            assert_eq!(Sha1::digest(piece).as_slice(), expected_hash);
+            storage.write_piece(index, piece).unwrap();
+        }
+
+        assert_eq!(storage.read_piece(3).unwrap(), &content[96..]);
+        assert_eq!(storage.into_inner(), content);
+    }
+
+    #[test]
+    fn should_reject_pieces_with_the_wrong_size_or_index() {
+        let content = content(100);
+        let meta_info = synthetic_torrent(&content, 32, &[]);
+        let mut storage = MemoryStorage::new(&meta_info.info);
+
+        assert!(storage.write_piece(0, &content[..31]).is_err());
+        assert!(storage.write_piece(3, &content[96..100]).is_ok());
+        assert!(storage.write_piece(4, &content[..32]).is_err());
+    }
+
+    #[test]
+    fn should_write_pieces_across_multiple_files() {
+        let content = content(100);
+        let meta_info = synthetic_torrent(&content, 32, &[("a.bin", 40), ("dir/b.bin", 60)]);
+        let root = std::env::temp_dir().join(format!("rustorrent-storage-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root, &meta_info.info);
+
+        for (index, piece) in content.chunks(32).enumerate() {
+            storage.write_piece(index, piece).unwrap();
+        }
+
+        assert_eq!(storage.read_piece(1).unwrap(), &content[32..64]);
+        let paths: Vec<&Path> = storage.paths().collect();
+        assert_eq!(fs::read(paths[0]).unwrap(), &content[..40]);
+        assert_eq!(fs::read(paths[1]).unwrap(), &content[40..]);
+        assert!(paths[1].ends_with("synthetic/dir/b.bin"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_report_exactly_the_corrupt_pieces() {
+        let content = content(100);
+        let meta_info = synthetic_torrent(&content, 16, &[("a.bin", 30), ("b.bin", 70)]);
+        let root = std::env::temp_dir().join(format!("rustorrent-verify-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root, &meta_info.info);
+
+        for (index, piece) in content.chunks(16).enumerate() {
+            storage.write_piece(index, piece).unwrap();
+        }
+        assert!(corrupt_pieces(&meta_info.info, &mut storage).is_empty());
+
+        // Flip a byte within piece 1 (first file) and piece 5 (second file)
+        let paths: Vec<PathBuf> = storage.paths().map(Path::to_path_buf).collect();
+        let mut first = fs::read(&paths[0]).unwrap();
+        first[20] ^= 0xff;
+        fs::write(&paths[0], first).unwrap();
+        let mut second = fs::read(&paths[1]).unwrap();
+        second[82 - 30] ^= 0xff;
+        fs::write(&paths[1], second).unwrap();
+
+        assert_eq!(corrupt_pieces(&meta_info.info, &mut storage), vec![1, 5]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_report_unreadable_pieces_as_corrupt() {
+        let content = content(40);
+        let meta_info = synthetic_torrent(&content, 16, &[]);
+        let mut storage = MemoryStorage::new(&meta_info.info);
+        storage.write_piece(1, &content[16..32]).unwrap();
+
+        assert_eq!(corrupt_pieces(&meta_info.info, &mut storage), vec![0, 2]);
+
+        let root = std::env::temp_dir().join(format!("rustorrent-missing-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root, &meta_info.info);
+        assert_eq!(corrupt_pieces(&meta_info.info, &mut storage), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn should_preallocate_sparse_files_and_write_at_high_offsets() {
+        const LENGTH: u64 = 1 << 30;
+        const PIECE_LENGTH: u64 = 1 << 20;
+        let num_pieces = (LENGTH / PIECE_LENGTH) as usize;
+        let info = Bencode::Dict(IndexMap::from([
+            (ByteString::new("length"), Bencode::Number(LENGTH as i64)),
+            (
+                ByteString::new("name"),
+                Bencode::Text(ByteString::new("big.iso")),
+            ),
+            (
+                ByteString::new("piece length"),
+                Bencode::Number(PIECE_LENGTH as i64),
+            ),
+            (
+                ByteString::new("pieces"),
+                Bencode::Text(ByteString::from_vec(vec![0; num_pieces * 20])),
+            ),
+        ]));
+        let info = Info::try_from(&info).unwrap();
+        let root = std::env::temp_dir().join(format!("rustorrent-sparse-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root, &info);
+
+        storage.preallocate().unwrap();
+        let path = storage.paths().next().unwrap().to_path_buf();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.len(), LENGTH);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // blocks are 512 bytes, far less than the whole length is allocated
+            assert!(metadata.blocks() * 512 < LENGTH / 2);
+        }
+
+        let piece = content(PIECE_LENGTH as usize);
+        let last = num_pieces - 1;
+        storage.write_piece(last, &piece).unwrap();
+        assert_eq!(storage.read_piece(last).unwrap(), piece);
+        assert_eq!(
+            storage.read_piece(0).unwrap(),
+            vec![0; PIECE_LENGTH as usize]
+        );
+
+        // preallocating again, e.g. when resuming, keeps what was written
+        storage.preallocate().unwrap();
+        assert_eq!(storage.read_piece(last).unwrap(), piece);
+        assert_eq!(fs::metadata(&path).unwrap().len(), LENGTH);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "md5")]
+    #[test]
+    fn should_verify_the_md5sum_of_a_file() {
+        let path = std::env::temp_dir().join(format!("rustorrent-md5-{}", std::process::id()));
+        fs::write(&path, b"The quick brown fox jumps over the lazy dog").unwrap();
+        let file = |md5sum: Option<&str>| SingleFile {
+            name: String::from("fox.txt"),
+            length: 43,
+            md5sum: md5sum.map(String::from),
+        };
+
+        assert!(verify_md5(&file(Some("9e107d9d372bb6826bd81d3542a419d6")), &path).unwrap());
+        assert!(verify_md5(&file(Some("9E107D9D372BB6826BD81D3542A419D6")), &path).unwrap());
+        assert!(!verify_md5(&file(Some("d41d8cd98f00b204e9800998ecf8427e")), &path).unwrap());
+        assert!(verify_md5(&file(None), &path).is_err());
+
+        fs::remove_file(&path).unwrap();
+        assert!(verify_md5(&file(Some("9e107d9d372bb6826bd81d3542a419d6")), &path).is_err());
+    }
+}
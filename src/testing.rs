@@ -0,0 +1,218 @@
+//! Helpers to test code built on top of this crate
+//! without talking to a real tracker.
+//!
+//! Only available with the `test-util` feature.
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use crate::parser::announce_info::AnnounceInfo;
+use crate::parser::bencode::{Bencode, BencodeParser};
+use crate::parser::byte_string::ByteString;
+
+/// HTTP tracker serving canned announce and scrape responses.
+///
+/// The server listens on a random local port and shuts down when dropped.
+/// Responses are served from the `/announce` and `/scrape` paths,
+/// so [`MockTracker::announce_url`] can be handed to the client under test.
+/// Until a response is configured, the endpoint answers with a 404.
+pub struct MockTracker {
+    server: MockServer,
+    announce: CannedResponse,
+    scrape: CannedResponse,
+}
+
+impl MockTracker {
+    pub async fn start() -> Self {
+        let tracker = Self {
+            server: MockServer::start().await,
+            announce: CannedResponse::default(),
+            scrape: CannedResponse::default(),
+        };
+        for (endpoint, response) in [
+            ("/announce", &tracker.announce),
+            ("/scrape", &tracker.scrape),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .respond_with(response.clone())
+                .mount(&tracker.server)
+                .await;
+        }
+        tracker
+    }
+
+    pub fn announce_url(&self) -> String {
+        format!("{}/announce", self.server.uri())
+    }
+
+    pub fn scrape_url(&self) -> String {
+        format!("{}/scrape", self.server.uri())
+    }
+
+    /// Answer announces with the peers in the dictionary model
+    pub fn serve_announce(&self, announce_info: &AnnounceInfo) {
+        self.announce.set(&announce_info.to_bencode());
+    }
+
+    /// Answer announces with the peers in the compact format (BEP 23),
+    /// IPv6 peers being sent in `peers6` (BEP 7).
+    ///
+    /// # Panics
+    ///
+    /// If a peer IP isn't an IP address, as hostnames can't be compacted,
+    /// or if a peer port doesn't fit in the 2 bytes of a compact peer.
+    pub fn serve_compact_announce(&self, announce_info: &AnnounceInfo) {
+        let mut peers = Vec::new();
+        let mut peers6 = Vec::new();
+        for peer in &announce_info.peers {
+            let ip: IpAddr = peer
+                .ip
+                .parse()
+                .unwrap_or_else(|_| panic!("peer IP '{}' can't be compacted", peer.ip));
+            let port = u16::try_from(peer.port)
+                .unwrap_or_else(|_| panic!("peer port {} can't be compacted", peer.port))
+                .to_be_bytes();
+            match ip {
+                IpAddr::V4(ip) => {
+                    peers.extend(ip.octets());
+                    peers.extend(port);
+                }
+                IpAddr::V6(ip) => {
+                    peers6.extend(ip.octets());
+                    peers6.extend(port);
+                }
+            }
+        }
+
+        let Bencode::Dict(mut map) = announce_info.to_bencode() else {
+            unreachable!("announce info is always encoded as a dict");
+        };
+        map.insert(
+            ByteString::new("peers"),
+            Bencode::Text(ByteString::from_vec(peers)),
+        );
+        if !peers6.is_empty() {
+            map.insert(
+                ByteString::new("peers6"),
+                Bencode::Text(ByteString::from_vec(peers6)),
+            );
+        }
+        self.announce.set(&Bencode::Dict(map));
+    }
+
+    /// Reject announces with the given `failure reason`
+    pub fn serve_failure(&self, reason: &str) {
+        let response = Bencode::Dict(IndexMap::from([(
+            ByteString::new("failure reason"),
+            Bencode::Text(ByteString::new(reason)),
+        )]));
+        self.announce.set(&response);
+    }
+
+    /// Answer scrapes with the given response,
+    /// usually a dict with the stats of each torrent under `files`.
+    pub fn serve_scrape(&self, response: &Bencode) {
+        self.scrape.set(response);
+    }
+
+    /// Number of announces received so far
+    pub async fn announce_count(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|request| request.url.path() == "/announce")
+            .count()
+    }
+}
+
+/// Body served by an endpoint, which can be replaced while the server is running
+#[derive(Clone, Default)]
+struct CannedResponse(Arc<Mutex<Option<Vec<u8>>>>);
+
+impl CannedResponse {
+    fn set(&self, response: &Bencode) {
+        *self.0.lock().unwrap() = Some(BencodeParser::encode(response));
+    }
+}
+
+impl Respond for CannedResponse {
+    fn respond(&self, _request: &Request) -> ResponseTemplate {
+        match &*self.0.lock().unwrap() {
+            Some(body) => ResponseTemplate::new(200).set_body_bytes(body.clone()),
+            None => ResponseTemplate::new(404),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::announce_info::Peer;
+
+    use super::*;
+
+    fn peer(ip: &str, port: u64) -> Peer {
+        Peer {
+            peer_id: String::new(),
+            ip: ip.to_string(),
+            port,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_serve_a_compact_peers_response() {
+        let tracker = MockTracker::start().await;
+        let announce_info = AnnounceInfo {
+            interval: 1800,
+            complete: 2,
+            incomplete: 1,
+            peers: vec![peer("10.0.0.1", 6881), peer("2001:db8::1", 51413)],
+            min_interval: None,
+            tracker_id: None,
+            warning_message: None,
+        };
+        tracker.serve_compact_announce(&announce_info);
+
+        let body = reqwest::get(format!("{}?compact=1", tracker.announce_url()))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+
+        let response = BencodeParser::decode(&body).unwrap();
+        let Bencode::Dict(map) = &response else {
+            panic!("expected a dict, got {:?}", response);
+        };
+        assert_eq!(
+            map.get(&ByteString::new("peers")),
+            Some(&Bencode::Text(ByteString::from_vec(vec![
+                10, 0, 0, 1, 0x1a, 0xe1
+            ])))
+        );
+        assert_eq!(AnnounceInfo::parse(&response).unwrap(), announce_info);
+        assert_eq!(tracker.announce_count().await, 1);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "peer port 70000 can't be compacted")]
+    async fn should_refuse_to_truncate_ports() {
+        let tracker = MockTracker::start().await;
+        let announce_info = AnnounceInfo {
+            interval: 1800,
+            complete: 1,
+            incomplete: 0,
+            peers: vec![peer("10.0.0.1", 70000)],
+            min_interval: None,
+            tracker_id: None,
+            warning_message: None,
+        };
+        tracker.serve_compact_announce(&announce_info);
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+
+use crate::hash;
+use crate::parser::bencode::Bencode;
+use crate::parser::byte_string::ByteString;
+
+/// Piece length used unless [`TorrentBuilder::piece_length`] says otherwise
+pub const DEFAULT_PIECE_LENGTH: u64 = 256 * 1024;
+
+/// Create a new torrent out of a file on disk.
+/// Built with fluent setters, e.g.
+/// `TorrentBuilder::new("ubuntu.iso").piece_length(1 << 20).private(true)`
+#[derive(Debug, Clone)]
+pub struct TorrentBuilder {
+    path: PathBuf,
+    name: Option<String>,
+    piece_length: u64,
+    private: bool,
+}
+
+impl TorrentBuilder {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            name: None,
+            piece_length: DEFAULT_PIECE_LENGTH,
+            private: false,
+        }
+    }
+
+    /// Name of the torrent, defaults to the file name
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// # Panics
+    ///
+    /// If the piece length is 0.
+    pub fn piece_length(mut self, piece_length: u64) -> Self {
+        assert!(piece_length > 0, "the piece length must not be 0");
+        self.piece_length = piece_length;
+        self
+    }
+
+    pub fn private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    /// Build the `info` dict of the torrent, hashing the file piece by piece.
+    /// Keys are in canonical (sorted) order, so encoding the dict gives
+    /// the bytes other clients compute the info_hash from.
+    pub fn info_dict(&self) -> io::Result<Bencode> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => self
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} has no file name", self.path.display()),
+                    )
+                })?,
+        };
+
+        let mut file = File::open(&self.path)?;
+        let mut length = 0;
+        let mut pieces = Vec::new();
+        let mut piece = Vec::new();
+        loop {
+            piece.clear();
+            (&mut file)
+                .take(self.piece_length)
+                .read_to_end(&mut piece)?;
+            if piece.is_empty() {
+                break;
+            }
+            length += piece.len() as u64;
+            pieces.extend(hash::sha1(&piece));
+        }
+
+        let mut info = IndexMap::from([
+            (ByteString::new("length"), Bencode::Number(length as i64)),
+            (
+                ByteString::new("name"),
+                Bencode::Text(ByteString::new(&name)),
+            ),
+            (
+                ByteString::new("piece length"),
+                Bencode::Number(self.piece_length as i64),
+            ),
+            (
+                ByteString::new("pieces"),
+                Bencode::Text(ByteString::from_vec(pieces)),
+            ),
+        ]);
+        if self.private {
+            info.insert(ByteString::new("private"), Bencode::Number(1));
+        }
+        Ok(Bencode::Dict(info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::parser::bencode::BencodeParser;
+    use crate::parser::meta_info::MetaInfo;
+
+    use super::*;
+
+    #[test]
+    fn should_build_an_info_dict_from_a_file() {
+        let path = std::env::temp_dir().join(format!("rustorrent-builder-{}", std::process::id()));
+        let content: Vec<u8> = (0..40_000u32).map(|i| i as u8).collect();
+        fs::write(&path, &content).unwrap();
+
+        let value = TorrentBuilder::new(&path)
+            .name("content.bin")
+            .piece_length(16384)
+            .private(true)
+            .info_dict()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let Bencode::Dict(dict) = &value else {
+            panic!("expected a dict");
+        };
+        let keys: Vec<String> = dict.keys().map(ByteString::to_string).collect();
+        assert_eq!(
+            keys,
+            vec!["length", "name", "piece length", "pieces", "private"]
+        );
+
+        let mut torrent = b"d8:announce23:http://tracker.example/4:info".to_vec();
+        torrent.extend(BencodeParser::encode(&value));
+        torrent.push(b'e');
+        let info = MetaInfo::from_bytes(&torrent).unwrap().info;
+        assert_eq!(info.num_pieces(), 3);
+        assert_eq!(info.total_length(), 40_000);
+        assert_eq!(info.name(), "content.bin");
+        assert!(info.private);
+        assert!(info.validate_length().is_ok());
+        assert_eq!(
+            info.piece_hash_iter().nth(2),
+            Some(&hash::sha1(&content[32768..])[..])
+        );
+        assert_eq!(info.info_hash(), hash::sha1(&BencodeParser::encode(&value)));
+    }
+}
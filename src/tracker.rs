@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use reqwest::Client;
+
+use crate::http_tracker::{AnnounceRequest, HTTPTracker};
+use crate::parser::announce_info::AnnounceInfo;
+#[cfg(feature = "udp-tracker")]
+use crate::udp_tracker::UDPTracker;
+
+/// The announce URL uses a scheme no tracker client is available for,
+/// e.g. `dht://` or `udp://` without the `udp-tracker` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedScheme {
+    pub url: String,
+}
+
+impl Error for UnsupportedScheme {}
+
+impl Display for UnsupportedScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no tracker client supports the announce URL '{}'",
+            self.url
+        )
+    }
+}
+
+/// Tracker client matching the scheme of an announce URL,
+/// so callers don't have to know which transport a tracker speaks.
+pub enum Tracker {
+    Http(HTTPTracker),
+    #[cfg(feature = "udp-tracker")]
+    Udp(UDPTracker),
+}
+
+impl Tracker {
+    /// Pick the client for the announce URL: `http://` and `https://` URLs
+    /// are announced to with the given HTTP client, `udp://` ones over UDP.
+    pub fn for_url(
+        url: &str,
+        peer_id: [u8; 20],
+        http_client: Client,
+    ) -> Result<Self, UnsupportedScheme> {
+        let scheme = url
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+        match scheme.as_deref() {
+            Some("http" | "https") => Ok(Tracker::Http(HTTPTracker::new(peer_id, http_client))),
+            #[cfg(feature = "udp-tracker")]
+            Some("udp") => Ok(Tracker::Udp(UDPTracker::new(peer_id))),
+            _ => Err(UnsupportedScheme {
+                url: url.to_string(),
+            }),
+        }
+    }
+
+    /// Announce ourselves for the torrent identified by the given info_hash
+    pub async fn announce(
+        &self,
+        url: &str,
+        info_hash: &[u8; 20],
+        request: &AnnounceRequest,
+    ) -> Result<AnnounceInfo, Box<dyn Error>> {
+        match self {
+            Tracker::Http(tracker) => tracker.announce(url, info_hash, request).await,
+            #[cfg(feature = "udp-tracker")]
+            Tracker::Udp(tracker) => tracker.announce(url, info_hash, request).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    #[cfg(feature = "udp-tracker")]
+    use tokio::net::UdpSocket;
+    use wiremock::matchers::any;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::peer::peer_id::generate_peer_id;
+
+    use super::*;
+
+    #[test]
+    fn should_reject_unsupported_schemes() {
+        let Err(err) = Tracker::for_url("dht://abc", [1; 20], Client::new()) else {
+            panic!("expected dht:// to be unsupported");
+        };
+        assert_eq!(
+            err.to_string(),
+            "no tracker client supports the announce URL 'dht://abc'"
+        );
+        assert!(Tracker::for_url("tracker.example", [1; 20], Client::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_route_http_urls_to_the_http_tracker() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(fs::read("tests/announce_response").unwrap()),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let url = mock_server.uri();
+        let tracker = Tracker::for_url(&url, generate_peer_id("-RT0001-"), Client::new()).unwrap();
+        assert!(matches!(tracker, Tracker::Http(_)));
+        assert!(tracker
+            .announce(&url, &[1; 20], &AnnounceRequest::default())
+            .await
+            .is_ok());
+    }
+
+    #[cfg(feature = "udp-tracker")]
+    #[tokio::test]
+    async fn should_route_udp_urls_to_the_udp_tracker() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("UDP://{}/announce", socket.local_addr().unwrap());
+        // answer the connect request with an error, which only the UDP protocol can carry
+        let server = tokio::spawn(async move {
+            let mut buf = [0; 16];
+            let (_, from) = socket.recv_from(&mut buf).await.unwrap();
+            let mut response = 3u32.to_be_bytes().to_vec();
+            response.extend(&buf[12..16]);
+            response.extend(b"udp tracker");
+            socket.send_to(&response, from).await.unwrap();
+        });
+
+        let tracker = Tracker::for_url(&url, generate_peer_id("-RT0001-"), Client::new()).unwrap();
+        assert!(matches!(tracker, Tracker::Udp(_)));
+        let err = tracker
+            .announce(&url, &[1; 20], &AnnounceRequest::default())
+            .await
+            .unwrap_err();
+        server.await.unwrap();
+        assert_eq!(err.to_string(), "tracker failure: udp tracker");
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::Client;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::http_tracker::{AnnounceEvent, AnnounceRequest};
+use crate::parser::announce_info::{AnnounceInfo, Peer};
+use crate::parser::meta_info::MetaInfo;
+use crate::stats::SessionStats;
+use crate::tracker::Tracker;
+
+/// How long to wait before announcing again when every tracker failed
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Transfer progress reported to the trackers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub uploaded: u64,
+    pub downloaded: u64,
+    /// bytes still to be downloaded
+    pub left: u64,
+}
+
+/// Source of the progress sent with every announce,
+/// read right before each announce
+pub trait StatsProvider: Send + 'static {
+    fn progress(&self) -> Progress;
+}
+
+impl StatsProvider for Arc<Mutex<SessionStats>> {
+    fn progress(&self) -> Progress {
+        let stats = self.lock().unwrap();
+        Progress {
+            uploaded: stats.uploaded(),
+            downloaded: stats.downloaded(),
+            left: stats.total_length.saturating_sub(stats.downloaded()),
+        }
+    }
+}
+
+enum Command {
+    Completed,
+    Stop,
+}
+
+/// Drives the announce lifecycle of a torrent: `started` first, then
+/// periodic announces every `interval` (never sooner than `min interval`),
+/// `completed` once the download is done and `stopped` on shutdown.
+///
+/// Trackers are tried tier by tier as per BEP 12: within a tier, the first
+/// one to answer is moved to the front so it is tried first next time.
+pub struct TrackerLoop<S> {
+    tiers: Vec<Vec<String>>,
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    http_client: Client,
+    stats: S,
+    port: u16,
+    retry_interval: Duration,
+}
+
+impl<S: StatsProvider> TrackerLoop<S> {
+    pub fn new(meta_info: &MetaInfo, peer_id: [u8; 20], http_client: Client, stats: S) -> Self {
+        let tiers = match &meta_info.announce_list {
+            Some(tiers) => tiers.clone(),
+            // every tracker was removed
+            None if meta_info.announce.is_empty() => Vec::new(),
+            None => vec![vec![meta_info.announce.clone()]],
+        };
+        Self {
+            tiers,
+            info_hash: meta_info.info.info_hash(),
+            peer_id,
+            http_client,
+            stats,
+            port: AnnounceRequest::default().port,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+
+    /// Port we accept peer connections on
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Start announcing in a background task. The peers returned by
+    /// each announce are sent to the returned channel.
+    pub fn spawn(self) -> (TrackerLoopHandle, mpsc::UnboundedReceiver<Vec<Peer>>) {
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (peers_tx, peers_rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(self.run(command_rx, peers_tx));
+        let handle = TrackerLoopHandle {
+            commands: command_tx,
+            task,
+        };
+        (handle, peers_rx)
+    }
+
+    async fn run(
+        mut self,
+        mut commands: mpsc::UnboundedReceiver<Command>,
+        peers: mpsc::UnboundedSender<Vec<Peer>>,
+    ) {
+        // events are announced in order: `started` must be the first
+        // announce, even when `completed` comes before it went through
+        let mut events = VecDeque::from([AnnounceEvent::Started]);
+        loop {
+            let wait = match self.announce(events.front().copied()).await {
+                Some(announce_info) => {
+                    events.pop_front();
+                    let wait = Duration::from_secs(
+                        announce_info
+                            .interval
+                            .max(announce_info.min_interval.unwrap_or(0))
+                            .max(1),
+                    );
+                    // nobody listening for peers isn't a reason to leave the swarm
+                    let _ = peers.send(announce_info.peers);
+                    if !events.is_empty() {
+                        continue;
+                    }
+                    wait
+                }
+                // keep the events until they are announced
+                None => self.retry_interval,
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                command = commands.recv() => match command {
+                    Some(Command::Completed) => {
+                        if !events.contains(&AnnounceEvent::Completed) {
+                            events.push_back(AnnounceEvent::Completed);
+                        }
+                    }
+                    Some(Command::Stop) | None => break,
+                },
+            }
+        }
+        self.announce(Some(AnnounceEvent::Stopped)).await;
+    }
+
+    /// Announce to the first tracker answering, tier by tier
+    async fn announce(&mut self, event: Option<AnnounceEvent>) -> Option<AnnounceInfo> {
+        let progress = self.stats.progress();
+        let request = AnnounceRequest {
+            event,
+            ..AnnounceRequest::default()
+                .port(self.port)
+                .uploaded(progress.uploaded)
+                .downloaded(progress.downloaded)
+                .left(progress.left)
+        };
+
+        for tier in &mut self.tiers {
+            for index in 0..tier.len() {
+                let Ok(tracker) =
+                    Tracker::for_url(&tier[index], self.peer_id, self.http_client.clone())
+                else {
+                    continue;
+                };
+                if let Ok(announce_info) = tracker
+                    .announce(&tier[index], &self.info_hash, &request)
+                    .await
+                {
+                    let url = tier.remove(index);
+                    tier.insert(0, url);
+                    return Some(announce_info);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Controls a running [`TrackerLoop`]
+pub struct TrackerLoopHandle {
+    commands: mpsc::UnboundedSender<Command>,
+    task: JoinHandle<()>,
+}
+
+impl TrackerLoopHandle {
+    /// The download is complete: announce `completed` right away
+    pub fn completed(&self) {
+        let _ = self.commands.send(Command::Completed);
+    }
+
+    /// Announce `stopped` and wait for the loop to end
+    pub async fn shutdown(self) {
+        let _ = self.commands.send(Command::Stop);
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::any;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::peer::peer_id::generate_peer_id;
+
+    use super::*;
+
+    struct FixedProgress;
+
+    impl StatsProvider for FixedProgress {
+        fn progress(&self) -> Progress {
+            Progress {
+                uploaded: 1,
+                downloaded: 2,
+                left: 3,
+            }
+        }
+    }
+
+    const ANNOUNCE_RESPONSE: &[u8] = b"d8:completei1e10:incompletei0e8:intervali1e\
+        5:peersld2:ip8:10.0.0.57:peer id3:abc4:porti6881eeee";
+
+    async fn announced_events(tracker: &MockServer) -> Vec<Option<String>> {
+        let requests = tracker.received_requests().await.unwrap();
+        requests
+            .iter()
+            .map(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "event")
+                    .map(|(_, value)| value.into_owned())
+            })
+            .collect()
+    }
+
+    // time is paused, so the intervals elapse as soon as the loop waits
+    #[tokio::test(start_paused = true)]
+    async fn should_run_the_announce_lifecycle_with_failover() {
+        let failing = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&failing)
+            .await;
+        let tracker = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(ANNOUNCE_RESPONSE))
+            .mount(&tracker)
+            .await;
+
+        let mut meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        meta_info.announce_list = Some(vec![
+            vec![format!("{}/announce", failing.uri()), tracker.uri()],
+            vec![String::from("http://tracker.invalid/announce")],
+        ]);
+        let (handle, mut peers) = TrackerLoop::new(
+            &meta_info,
+            generate_peer_id("-RT0001-"),
+            Client::new(),
+            FixedProgress,
+        )
+        .port(6881)
+        .spawn();
+
+        // started, then a regular announce once the 1s interval elapsed
+        for _ in 0..2 {
+            let batch = peers.recv().await.unwrap();
+            assert_eq!(batch[0].ip, "10.0.0.5");
+        }
+        handle.completed();
+        assert_eq!(peers.recv().await.unwrap().len(), 1);
+        handle.shutdown().await;
+
+        assert_eq!(
+            announced_events(&tracker).await,
+            vec![
+                Some(String::from("started")),
+                None,
+                Some(String::from("completed")),
+                Some(String::from("stopped")),
+            ]
+        );
+        let requests = tracker.received_requests().await.unwrap();
+        assert!(requests[0]
+            .url
+            .query_pairs()
+            .any(|(key, value)| key == "left" && value == "3"));
+        // once the second tracker answered, it is tried first
+        assert_eq!(failing.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_announce_started_before_completed() {
+        let tracker = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&tracker)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(ANNOUNCE_RESPONSE))
+            .mount(&tracker)
+            .await;
+
+        let mut meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+        meta_info.announce_list = Some(vec![vec![tracker.uri()]]);
+        let (handle, mut peers) = TrackerLoop::new(
+            &meta_info,
+            generate_peer_id("-RT0001-"),
+            Client::new(),
+            FixedProgress,
+        )
+        .spawn();
+
+        // completed while the first `started` fails
+        handle.completed();
+        for _ in 0..2 {
+            assert_eq!(peers.recv().await.unwrap().len(), 1);
+        }
+        handle.shutdown().await;
+
+        assert_eq!(
+            announced_events(&tracker).await,
+            vec![
+                Some(String::from("started")),
+                Some(String::from("started")),
+                Some(String::from("completed")),
+                Some(String::from("stopped")),
+            ]
+        );
+    }
+}
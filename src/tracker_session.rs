@@ -0,0 +1,252 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use tokio::runtime::Handle;
+
+use crate::http_tracker::{AnnounceEvent, AnnounceRequest};
+use crate::parser::announce_info::AnnounceInfo;
+use crate::tracker::Tracker;
+
+/// An announce session with a tracker for a single torrent.
+///
+/// Dropping the session fires a best-effort `stopped` announce in the
+/// background, so the tracker doesn't keep counting us in the swarm when
+/// a session ends abruptly. Call [`TrackerSession::stop`] to send it
+/// explicitly and wait for the tracker's answer instead.
+pub struct TrackerSession {
+    announce_url: String,
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    http_client: Client,
+    /// last request sent, so the final `stopped` announce
+    /// reports the latest known progress
+    last_request: AnnounceRequest,
+    /// when the last successful announce was made
+    last_announce: Option<Instant>,
+    /// `min interval` of the last response, the tracker doesn't want
+    /// to hear from us more often than that
+    min_interval: Option<Duration>,
+    stopped: bool,
+}
+
+/// A forced announce was refused as it would come sooner than
+/// the tracker's `min interval` allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnnounceTooSoon {
+    /// how long to wait before forcing an announce again
+    pub retry_in: Duration,
+}
+
+impl Error for AnnounceTooSoon {}
+
+impl Display for AnnounceTooSoon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "announce too soon, min interval allows one in {:?}",
+            self.retry_in
+        )
+    }
+}
+
+impl TrackerSession {
+    pub fn new(
+        announce_url: &str,
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+        http_client: Client,
+    ) -> Self {
+        Self {
+            announce_url: announce_url.to_string(),
+            info_hash,
+            peer_id,
+            http_client,
+            last_request: AnnounceRequest::default(),
+            last_announce: None,
+            min_interval: None,
+            stopped: false,
+        }
+    }
+
+    pub async fn announce(
+        &mut self,
+        request: AnnounceRequest,
+    ) -> Result<AnnounceInfo, Box<dyn std::error::Error>> {
+        let announce_info = self
+            .tracker()?
+            .announce(&self.announce_url, &self.info_hash, &request)
+            .await;
+        self.last_request = request;
+        if let Ok(announce_info) = &announce_info {
+            self.last_announce = Some(Instant::now());
+            self.min_interval = announce_info.min_interval.map(Duration::from_secs);
+        }
+        announce_info
+    }
+
+    /// Announce right away with the latest progress instead of waiting for
+    /// the next interval, e.g. to look for more peers. The tracker's
+    /// `min interval` is still honoured: forcing an announce sooner than
+    /// that fails with [`AnnounceTooSoon`].
+    pub async fn force_announce(&mut self) -> Result<AnnounceInfo, Box<dyn Error>> {
+        if let (Some(last_announce), Some(min_interval)) = (self.last_announce, self.min_interval) {
+            let elapsed = last_announce.elapsed();
+            if elapsed < min_interval {
+                return Err(Box::new(AnnounceTooSoon {
+                    retry_in: min_interval - elapsed,
+                }));
+            }
+        }
+
+        let request = AnnounceRequest {
+            event: None,
+            ..self.last_request.clone()
+        };
+        self.announce(request).await
+    }
+
+    /// Tell the tracker we are leaving the swarm
+    pub async fn stop(mut self) -> Result<AnnounceInfo, Box<dyn std::error::Error>> {
+        self.stopped = true;
+        let request = self.stopped_request();
+        self.tracker()?
+            .announce(&self.announce_url, &self.info_hash, &request)
+            .await
+    }
+
+    /// Client for the transport of the announce URL (HTTP, UDP...)
+    fn tracker(&self) -> Result<Tracker, Box<dyn Error>> {
+        Ok(Tracker::for_url(
+            &self.announce_url,
+            self.peer_id,
+            self.http_client.clone(),
+        )?)
+    }
+
+    fn stopped_request(&self) -> AnnounceRequest {
+        self.last_request.clone().event(AnnounceEvent::Stopped)
+    }
+}
+
+impl Drop for TrackerSession {
+    fn drop(&mut self) {
+        if self.stopped {
+            return;
+        }
+
+        // Without a runtime (e.g. dropped during shutdown)
+        // there is no way to reach the tracker anymore.
+        let Ok(runtime) = Handle::try_current() else {
+            return;
+        };
+
+        let Ok(tracker) = self.tracker() else {
+            return;
+        };
+        let request = self.stopped_request();
+        let announce_url = std::mem::take(&mut self.announce_url);
+        let info_hash = self.info_hash;
+        runtime.spawn(async move {
+            // best-effort: nobody is left to handle a failure
+            let _ = tracker.announce(&announce_url, &info_hash, &request).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use std::time::Duration;
+
+    use wiremock::matchers::{any, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::peer::peer_id::generate_peer_id;
+
+    use super::*;
+
+    async fn mock_tracker() -> MockServer {
+        let announce_response = fs::read("tests/announce_response").unwrap();
+        let mock_server = MockServer::start().await;
+        Mock::given(query_param("event", "stopped"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(announce_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn should_announce_stopped_when_dropped() {
+        let mock_server = mock_tracker().await;
+
+        let session = TrackerSession::new(
+            &mock_server.uri(),
+            [1; 20],
+            generate_peer_id("-RT0001-"),
+            Client::new(),
+        );
+        drop(session);
+
+        for _ in 0..100 {
+            if !mock_server.received_requests().await.unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn should_only_announce_stopped_once_when_stopped_explicitly() {
+        let mock_server = mock_tracker().await;
+
+        let session = TrackerSession::new(
+            &mock_server.uri(),
+            [1; 20],
+            generate_peer_id("-RT0001-"),
+            Client::new(),
+        );
+        assert!(session.stop().await.is_ok());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn should_only_force_announces_after_the_min_interval() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(200).set_body_bytes(
+                    b"d8:completei1e10:incompletei0e8:intervali1800e12:min intervali1e5:peerslee"
+                        .to_vec(),
+                ),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let mut session = TrackerSession::new(
+            &mock_server.uri(),
+            [1; 20],
+            generate_peer_id("-RT0001-"),
+            Client::new(),
+        );
+        let announce_info = session.announce(AnnounceRequest::default()).await.unwrap();
+        assert_eq!(announce_info.min_interval, Some(1));
+
+        let err = session.force_announce().await.unwrap_err();
+        let too_soon = err.downcast_ref::<AnnounceTooSoon>().unwrap();
+        assert!(too_soon.retry_in <= Duration::from_secs(1));
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(session.force_announce().await.is_ok());
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+
+        assert!(session.stop().await.is_ok());
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use tokio::net::{lookup_host, UdpSocket};
+use tokio::time::{timeout_at, Instant};
+
+use crate::http_tracker::{AnnounceEvent, AnnounceRequest};
+use crate::parser::announce_info::{AnnounceError, AnnounceInfo, Peer};
+use crate::parser::byte_string::ByteString;
+
+/// Magic constant identifying the protocol in connect requests
+const PROTOCOL_ID: u64 = 0x41727101980;
+
+const ACTION_CONNECT: u32 = 0;
+const ACTION_ANNOUNCE: u32 = 1;
+const ACTION_ERROR: u32 = 3;
+
+/// Biggest datagram we expect, enough for a few hundred peers
+const MAX_PACKET_SIZE: usize = 64 * 1024;
+
+/// Handle UDP trackers (`udp://` announce URLs), following
+/// [BEP 15](https://www.bittorrent.org/beps/bep_0015.html).
+///
+/// Every announce first gets a connection id from the tracker, then sends
+/// the announce itself. UDP being unreliable, a request without a response
+/// is sent again, waiting twice as long each time.
+pub struct UDPTracker {
+    peer_id: [u8; 20],
+    /// how long to wait for the first response
+    timeout: Duration,
+    /// how many times an unanswered request is sent again
+    retries: u32,
+}
+
+impl UDPTracker {
+    /// Create a tracker announcing ourselves with the given peer id
+    /// (see [`generate_peer_id`](crate::peer::peer_id::generate_peer_id)).
+    /// Requests are retried up to 8 times, starting with a 15 seconds
+    /// timeout, as recommended by the BEP.
+    pub fn new(peer_id: [u8; 20]) -> Self {
+        Self::with_timeout(peer_id, Duration::from_secs(15), 8)
+    }
+
+    /// Create a tracker waiting `timeout` for the first response to a request,
+    /// then doubling it for each of the `retries` times it is sent again.
+    pub fn with_timeout(peer_id: [u8; 20], timeout: Duration, retries: u32) -> Self {
+        Self {
+            peer_id,
+            timeout,
+            retries,
+        }
+    }
+
+    /// Announce ourselves for the torrent identified by the given info_hash
+    /// to the tracker at `url` (`udp://host:port`, an optional path is ignored).
+    pub async fn announce(
+        &self,
+        url: &str,
+        info_hash: &[u8; 20],
+        request: &AnnounceRequest,
+    ) -> Result<AnnounceInfo, Box<dyn Error>> {
+        let socket = Self::connect_socket(url).await?;
+
+        let transaction_id = new_transaction_id();
+        let mut connect = Vec::with_capacity(16);
+        connect.extend(PROTOCOL_ID.to_be_bytes());
+        connect.extend(ACTION_CONNECT.to_be_bytes());
+        connect.extend(transaction_id.to_be_bytes());
+        let response = self
+            .send(&socket, &connect, ACTION_CONNECT, transaction_id)
+            .await?;
+        let Some(connection_id) = response.get(..8) else {
+            return Err(malformed("connect response is too short"));
+        };
+
+        let transaction_id = new_transaction_id();
+        let announce = announce_packet(
+            connection_id,
+            transaction_id,
+            info_hash,
+            &self.peer_id,
+            request,
+        );
+        let response = self
+            .send(&socket, &announce, ACTION_ANNOUNCE, transaction_id)
+            .await?;
+        parse_announce_response(&response, socket.peer_addr()?)
+    }
+
+    async fn connect_socket(url: &str) -> Result<UdpSocket, Box<dyn Error>> {
+        let host = match url.split_once("://") {
+            Some((scheme, host)) if scheme.eq_ignore_ascii_case("udp") => host,
+            _ => return Err(format!("'{}' is not a UDP tracker URL", url).into()),
+        };
+        let host = host.split('/').next().unwrap_or(host);
+        let Some(addr) = lookup_host(host).await?.next() else {
+            return Err(format!("could not resolve tracker host '{}'", host).into());
+        };
+
+        let local_addr: SocketAddr = if addr.is_ipv4() {
+            ([0; 4], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local_addr).await?;
+        socket.connect(addr).await?;
+        Ok(socket)
+    }
+
+    /// Send the request until a response with the same transaction id comes
+    /// back, and return what follows its header. Responses to other requests
+    /// (e.g. late answers to previous attempts) are ignored.
+    async fn send(
+        &self,
+        socket: &UdpSocket,
+        packet: &[u8],
+        action: u32,
+        transaction_id: u32,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+        for attempt in 0..=self.retries {
+            socket.send(packet).await?;
+            let deadline = Instant::now() + self.timeout.saturating_mul(1 << attempt.min(16));
+            while let Ok(received) = timeout_at(deadline, socket.recv(&mut buf)).await {
+                let response = &buf[..received?];
+                if response.len() < 8 || read_u32(&response[4..]) != transaction_id {
+                    continue;
+                }
+                let body = &response[8..];
+                return match read_u32(response) {
+                    ACTION_ERROR => Err(Box::new(AnnounceError::TrackerFailure(
+                        String::from_utf8_lossy(body).into_owned(),
+                    ))),
+                    response_action if response_action == action => Ok(body.to_vec()),
+                    response_action => Err(malformed(&format!(
+                        "expected action {}, got {}",
+                        action, response_action
+                    ))),
+                };
+            }
+        }
+        Err(Box::new(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "no response from the tracker after {} attempts",
+                self.retries + 1
+            ),
+        )))
+    }
+}
+
+fn announce_packet(
+    connection_id: &[u8],
+    transaction_id: u32,
+    info_hash: &[u8; 20],
+    peer_id: &[u8; 20],
+    request: &AnnounceRequest,
+) -> Vec<u8> {
+    let event: u32 = match request.event {
+        None => 0,
+        Some(AnnounceEvent::Completed) => 1,
+        Some(AnnounceEvent::Started) => 2,
+        Some(AnnounceEvent::Stopped) => 3,
+    };
+    // 0 lets the tracker use the address the request comes from
+    let ip = request
+        .ip
+        .as_deref()
+        .and_then(|ip| ip.parse::<Ipv4Addr>().ok())
+        .map_or(0, u32::from);
+    // the key is a number here, anything else isn't sent
+    let key: u32 = request
+        .key
+        .as_deref()
+        .and_then(|key| key.parse().ok())
+        .unwrap_or(0);
+    // -1 lets the tracker pick how many peers to return
+    let numwant = request.numwant.map_or(-1, |numwant| numwant as i32);
+
+    let mut packet = Vec::with_capacity(98);
+    packet.extend(connection_id);
+    packet.extend(ACTION_ANNOUNCE.to_be_bytes());
+    packet.extend(transaction_id.to_be_bytes());
+    packet.extend(info_hash);
+    packet.extend(peer_id);
+    packet.extend(request.downloaded.to_be_bytes());
+    packet.extend(request.left.to_be_bytes());
+    packet.extend(request.uploaded.to_be_bytes());
+    packet.extend(event.to_be_bytes());
+    packet.extend(ip.to_be_bytes());
+    packet.extend(key.to_be_bytes());
+    packet.extend(numwant.to_be_bytes());
+    packet.extend(request.port.to_be_bytes());
+    packet
+}
+
+/// Parse the announce response following its header: interval, leechers
+/// and seeders, then peers in the compact format of the tracker's address family.
+fn parse_announce_response(
+    body: &[u8],
+    tracker_addr: SocketAddr,
+) -> Result<AnnounceInfo, Box<dyn Error>> {
+    if body.len() < 12 {
+        return Err(malformed("announce response is too short"));
+    }
+    let peers = ByteString::from_vec(body[12..].to_vec());
+    let peers = if tracker_addr.is_ipv4() {
+        Peer::parse_compact(&peers)?
+    } else {
+        Peer::parse_compact_v6(&peers)?
+    };
+    Ok(AnnounceInfo {
+        interval: read_u32(body) as u64,
+        incomplete: read_u32(&body[4..]) as u64,
+        complete: read_u32(&body[8..]) as u64,
+        peers,
+        min_interval: None,
+        tracker_id: None,
+        warning_message: None,
+    })
+}
+
+fn malformed(msg: &str) -> Box<dyn Error> {
+    Box::new(AnnounceError::MalformedResponse(msg.to_string()))
+}
+
+/// Read a big endian u32 from the first 4 bytes
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Random id matching responses to their request
+fn new_transaction_id() -> u32 {
+    RandomState::new().build_hasher().finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::peer::peer_id::generate_peer_id;
+
+    use super::*;
+
+    const CONNECTION_ID: [u8; 8] = [0xc0, 0xff, 0xee, 0, 0, 0, 0, 1];
+
+    /// Answer a connect and an announce request the way a tracker would,
+    /// returns the announce request received.
+    async fn serve_tracker(socket: UdpSocket, announce_response: Vec<u8>) -> Vec<u8> {
+        let mut buf = [0; 1024];
+
+        let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+        assert_eq!(len, 16);
+        assert_eq!(buf[..8], PROTOCOL_ID.to_be_bytes());
+        assert_eq!(read_u32(&buf[8..]), ACTION_CONNECT);
+        let mut response = ACTION_CONNECT.to_be_bytes().to_vec();
+        response.extend(&buf[12..16]);
+        response.extend(CONNECTION_ID);
+        socket.send_to(&response, from).await.unwrap();
+
+        let (len, from) = socket.recv_from(&mut buf).await.unwrap();
+        let request = buf[..len].to_vec();
+        // a stray response to some other request comes first
+        let mut response = ACTION_ANNOUNCE.to_be_bytes().to_vec();
+        response.extend(read_u32(&request[12..]).wrapping_add(1).to_be_bytes());
+        response.extend([0; 12]);
+        socket.send_to(&response, from).await.unwrap();
+
+        let mut response = ACTION_ANNOUNCE.to_be_bytes().to_vec();
+        response.extend(&request[12..16]);
+        response.extend(announce_response);
+        socket.send_to(&response, from).await.unwrap();
+        request
+    }
+
+    #[tokio::test]
+    async fn should_connect_then_announce() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("udp://{}/announce", socket.local_addr().unwrap());
+        let mut announce_response = Vec::new();
+        announce_response.extend(1800u32.to_be_bytes());
+        announce_response.extend(3u32.to_be_bytes());
+        announce_response.extend(7u32.to_be_bytes());
+        announce_response.extend([10, 0, 0, 1, 0x1a, 0xe1, 10, 0, 0, 2, 0x1a, 0xe2]);
+        let server = tokio::spawn(serve_tracker(socket, announce_response));
+
+        let peer_id = generate_peer_id("-RT0001-");
+        let tracker = UDPTracker::with_timeout(peer_id, Duration::from_secs(5), 0);
+        let announce_info = tracker
+            .announce(
+                &url,
+                &[7; 20],
+                &AnnounceRequest::default()
+                    .port(6881)
+                    .left(1024)
+                    .event(AnnounceEvent::Started),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(announce_info.interval, 1800);
+        assert_eq!(announce_info.incomplete, 3);
+        assert_eq!(announce_info.complete, 7);
+        let peers: Vec<(&str, u64)> = announce_info
+            .peers
+            .iter()
+            .map(|peer| (peer.ip.as_str(), peer.port))
+            .collect();
+        assert_eq!(peers, vec![("10.0.0.1", 6881), ("10.0.0.2", 6882)]);
+
+        let request = server.await.unwrap();
+        assert_eq!(request.len(), 98);
+        assert_eq!(request[..8], CONNECTION_ID);
+        assert_eq!(read_u32(&request[8..]), ACTION_ANNOUNCE);
+        assert_eq!(request[16..36], [7; 20]);
+        assert_eq!(request[36..56], peer_id);
+        assert_eq!(request[64..72], 1024u64.to_be_bytes());
+        assert_eq!(read_u32(&request[80..]), 2);
+        assert_eq!(request[96..], 6881u16.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn should_retry_unanswered_requests() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("udp://{}", socket.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            // the first connect request gets lost
+            socket.recv_from(&mut buf).await.unwrap();
+            let (_, from) = socket.recv_from(&mut buf).await.unwrap();
+            let mut response = ACTION_ERROR.to_be_bytes().to_vec();
+            response.extend(&buf[12..16]);
+            response.extend(b"unregistered torrent");
+            socket.send_to(&response, from).await.unwrap();
+        });
+
+        let tracker =
+            UDPTracker::with_timeout(generate_peer_id("-RT0001-"), Duration::from_millis(50), 1);
+        let err = tracker
+            .announce(&url, &[7; 20], &AnnounceRequest::default())
+            .await
+            .unwrap_err();
+        server.await.unwrap();
+
+        assert_eq!(err.to_string(), "tracker failure: unregistered torrent");
+    }
+
+    #[tokio::test]
+    async fn should_time_out_when_the_tracker_never_answers() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("udp://{}", socket.local_addr().unwrap());
+
+        let tracker =
+            UDPTracker::with_timeout(generate_peer_id("-RT0001-"), Duration::from_millis(10), 2);
+        let err = tracker
+            .announce(&url, &[7; 20], &AnnounceRequest::default())
+            .await
+            .unwrap_err();
+
+        let err = err.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
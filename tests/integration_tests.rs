@@ -1,13 +1,17 @@
 use std::{
     fs::{self, File},
-    io::Write,
+    io::{BufReader, Write},
     path::Path,
 };
 
+use sha1::{Digest, Sha1};
+
 use rustorrent::parser::{
-    bencode::BencodeParser,
+    announce_info::{AnnounceError, AnnounceInfo, Peer},
+    bencode::{Bencode, BencodeError, BencodeParser, DEFAULT_MAX_FILE_SIZE},
+    byte_string::ByteString,
     meta_info::MetaInfo,
-    meta_info::{FileMode, SingleFile},
+    meta_info::{FileMode, Info, SingleFile},
 };
 
 #[test]
@@ -48,6 +52,111 @@ fn can_decode_a_torrent_file_with_multiple_files() {
     // @TODO: Assert on file mode content for list of files
 }
 
+#[test]
+fn can_load_a_torrent_from_a_reader_with_its_original_info_bytes() {
+    let file = File::open("tests/ubuntu_sample.torrent").unwrap();
+    let meta_info = MetaInfo::from_reader(BufReader::new(file)).unwrap();
+
+    let info_hash: String = Sha1::digest(&meta_info.info.bencode_value)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    assert_eq!(info_hash, "99c82bb73505a3c0b453f9fa0e881d6e5a32a0c1");
+    assert_eq!(
+        meta_info.info.info_hash().as_slice(),
+        Sha1::digest(&meta_info.info.bencode_value).as_slice()
+    );
+}
+
+#[test]
+fn info_hash_only_matches_the_full_meta_info_hash() {
+    let bytes = fs::read("tests/ubuntu_sample.torrent").unwrap();
+    let meta_info = MetaInfo::from_bytes(&bytes).unwrap();
+
+    let info_hash = MetaInfo::info_hash_only(&bytes).unwrap();
+    assert_eq!(
+        info_hash.as_slice(),
+        Sha1::digest(&meta_info.info.bencode_value).as_slice()
+    );
+}
+
+#[test]
+fn re_encoding_the_info_dict_reproduces_the_original_bytes() {
+    let meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+    let Bencode::Dict(torrent) = BencodeParser::from_file("tests/ubuntu_sample.torrent").unwrap()
+    else {
+        panic!("expected the torrent to be a dict");
+    };
+
+    let info = torrent.get(&ByteString::new("info")).unwrap();
+    assert_eq!(BencodeParser::encode(info), meta_info.info.bencode_value);
+}
+
+#[test]
+fn can_iterate_over_piece_hashes() {
+    let meta_info = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+    let info = &meta_info.info;
+
+    assert!(info.num_pieces() > 0);
+    assert_eq!(info.piece_hash_iter().count(), info.num_pieces());
+    assert!(info.piece_hash_iter().all(|hash| hash.len() == 20));
+}
+
+#[test]
+fn piece_lengths_add_up_to_the_total_length() {
+    let info = MetaInfo::from_file("tests/ubuntu_sample.torrent")
+        .unwrap()
+        .info;
+
+    assert!(info.validate_length().is_ok());
+    assert_eq!(
+        (info.num_pieces() as u64 - 1) * info.piece_length + info.last_piece_length(),
+        info.total_length()
+    );
+}
+
+#[test]
+fn keeps_the_tiers_of_the_announce_list() {
+    let meta_info = MetaInfo::from_file("tests/haphead_bundle.torrent").unwrap();
+    let tiers = meta_info.announce_list.unwrap();
+
+    assert_eq!(tiers.len(), 6);
+    assert!(tiers.iter().all(|tier| tier.len() == 1));
+    assert_eq!(
+        tiers[0],
+        vec!["dht://3C9650FDF0E03236FD7CDB343FFB1F792342C11F.dht/announce"]
+    );
+    assert_eq!(tiers[5], vec!["udp://open.demonii.com:1337"]);
+
+    let mut bytes = b"d8:announce17:http://a/announce13:announce-list\
+        ll17:http://a/announce17:http://b/announceel17:http://c/announceee\
+        4:infod6:lengthi1e4:name1:a12:piece lengthi16384e6:pieces20:"
+        .to_vec();
+    bytes.extend([0; 20]);
+    bytes.extend(b"ee");
+    let tiers = MetaInfo::from_bytes(&bytes).unwrap().announce_list.unwrap();
+    assert_eq!(
+        tiers,
+        vec![
+            vec!["http://a/announce", "http://b/announce"],
+            vec!["http://c/announce"],
+        ]
+    );
+}
+
+#[test]
+fn total_length_covers_both_file_modes() {
+    let single = MetaInfo::from_file("tests/ubuntu_sample.torrent").unwrap();
+    assert_eq!(single.info.total_length(), 4071903232);
+
+    let multi = MetaInfo::from_file("tests/haphead_bundle.torrent").unwrap();
+    let FileMode::Multi(files) = &multi.info.file_info else {
+        panic!("expected a multi-file torrent");
+    };
+    assert_eq!(files.files.len(), 3);
+    assert_eq!(multi.info.total_length(), 117 + 117 + 861315578);
+}
+
 // Make sure that
 #[test]
 fn can_write_file() {
@@ -64,8 +173,104 @@ fn can_write_file() {
     assert_eq!(decoded_file, decoded_from_new_file);
 }
 
+#[test]
+fn can_decode_a_torrent_file_with_trailing_client_data() {
+    let mut content = fs::read("tests/ubuntu_sample.torrent").unwrap();
+    content.extend(b"d6:resumei1ee");
+
+    let file_path =
+        std::env::temp_dir().join(format!("rustorrent-trailing-{}", std::process::id()));
+    let file_path = file_path.to_str().unwrap();
+    fs::write(file_path, content).unwrap();
+
+    let meta_info = MetaInfo::from_file(file_path);
+    let strict = MetaInfo::from_file_strict(file_path);
+    fs::remove_file(file_path).unwrap();
+    assert_eq!(
+        &meta_info.unwrap().announce,
+        "https://torrent.ubuntu.com/announce"
+    );
+
+    assert!(strict.is_err());
+    assert!(MetaInfo::from_file_strict("tests/ubuntu_sample.torrent").is_ok());
+}
+
+#[test]
+fn should_refuse_to_read_files_over_the_maximum_size() {
+    let file_path =
+        std::env::temp_dir().join(format!("rustorrent-oversized-{}", std::process::id()));
+    let file_path = file_path.to_str().unwrap();
+    let file = File::create(file_path).unwrap();
+    file.set_len(DEFAULT_MAX_FILE_SIZE + 1).unwrap();
+
+    let err = BencodeParser::from_file(file_path).unwrap_err();
+    fs::remove_file(file_path).unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "file size of {} bytes exceeds the maximum of {} bytes",
+            DEFAULT_MAX_FILE_SIZE + 1,
+            DEFAULT_MAX_FILE_SIZE
+        )
+    );
+
+    assert!(MetaInfo::from_file_with_max_size("tests/ubuntu_sample.torrent", 1024).is_err());
+    assert!(MetaInfo::from_file_with_max_size("tests/ubuntu_sample.torrent", 1024 * 1024).is_ok());
+}
+
 #[test]
 fn should_parse_announce_response() {
     let decoded_announce_response = BencodeParser::from_file("tests/announce_response");
     assert!(decoded_announce_response.is_ok());
 }
+
+fn peer_addresses(announce_info: &AnnounceInfo) -> Vec<(&str, u64)> {
+    announce_info
+        .peers
+        .iter()
+        .map(|peer| (peer.ip.as_str(), peer.port))
+        .collect()
+}
+
+#[test]
+fn should_parse_compact_ipv4_announce_response() {
+    let value = BencodeParser::from_file("tests/announce_response_compact").unwrap();
+    let announce_info = AnnounceInfo::parse(&value).unwrap();
+
+    assert_eq!(announce_info.interval, 1800);
+    assert_eq!(
+        peer_addresses(&announce_info),
+        vec![
+            ("10.0.0.1", 6881),
+            ("192.168.1.20", 51413),
+            ("203.0.113.7", 6889)
+        ]
+    );
+}
+
+#[test]
+fn should_parse_compact_ipv6_announce_response() {
+    let value = BencodeParser::from_file("tests/announce_response_compact6").unwrap();
+    let announce_info = AnnounceInfo::parse(&value).unwrap();
+
+    assert_eq!(
+        peer_addresses(&announce_info),
+        vec![("::1", 6881), ("2001:db8::1", 51413)]
+    );
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+// Core types get shared across download tasks,
+// so make sure we don't accidentally lose thread-safety.
+#[test]
+fn core_types_are_send_and_sync() {
+    assert_send_sync::<MetaInfo>();
+    assert_send_sync::<Info>();
+    assert_send_sync::<AnnounceInfo>();
+    assert_send_sync::<Peer>();
+    assert_send_sync::<Bencode>();
+    assert_send_sync::<ByteString>();
+    assert_send_sync::<BencodeError>();
+    assert_send_sync::<AnnounceError>();
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "zeroize")]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    slice,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use rustorrent::parser::byte_string::{ByteString, SecretByteString};
+use zeroize::Zeroize;
+
+/// Allocator checking the contents of one watched allocation right
+/// before it is freed, which is the only point where it is still valid
+/// to read it.
+struct ProbeAllocator;
+
+static WATCHED: AtomicUsize = AtomicUsize::new(0);
+const NOT_FREED: u8 = 0;
+const FREED_ZEROED: u8 = 1;
+const FREED_DIRTY: u8 = 2;
+static WATCHED_STATE: AtomicU8 = AtomicU8::new(NOT_FREED);
+
+unsafe impl GlobalAlloc for ProbeAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ptr as usize == WATCHED.load(Ordering::SeqCst) {
+            let bytes = slice::from_raw_parts(ptr, layout.size());
+            let state = if bytes.iter().all(|&byte| byte == 0) {
+                FREED_ZEROED
+            } else {
+                FREED_DIRTY
+            };
+            WATCHED_STATE.store(state, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: ProbeAllocator = ProbeAllocator;
+
+#[test]
+fn secret_byte_strings_are_zeroed_before_being_freed() {
+    let passkey = SecretByteString::new(ByteString::new("0123456789abcdef0123456789abcdef"));
+    WATCHED.store(passkey.as_ptr() as usize, Ordering::SeqCst);
+
+    drop(passkey);
+
+    assert_eq!(WATCHED_STATE.load(Ordering::SeqCst), FREED_ZEROED);
+}
+
+#[test]
+fn byte_strings_are_zeroed_on_demand() {
+    let mut passkey = ByteString::new("0123456789abcdef");
+    passkey.zeroize();
+    assert!(passkey.is_empty());
+
+    // plain byte strings can still be moved out of
+    let bytes: Vec<u8> = ByteString::new("announce").0;
+    assert_eq!(bytes, b"announce");
+}